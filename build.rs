@@ -0,0 +1,315 @@
+// generates the packed assets in $OUT_DIR:
+//
+// sprites.rs: the sprite art of src/world/sprites.rs. every frame is
+// stored as a plain bitstream or run length encoded, whichever is
+// smaller. set SPRITE_REPORT=1 to print the sizes
+//
+// tunes.rs: the RTTTL ringtones of src/world/tunes.rtttl as tracker songs
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+struct Asset {
+    name: &'static str,
+    w: u32,
+    h: u32,
+    // 2 when the low plane rows follow the high plane ones
+    planes: u32,
+    // (first row, first bit from the left) of each frame
+    frames: &'static [(u32, u32)],
+}
+
+const ASSETS: &[Asset] = &[
+    Asset {
+        name: "ENEMY",
+        w: 11,
+        h: 8,
+        planes: 1,
+        frames: &[(24, 0), (24, 19)],
+    },
+    // level, going down, going up
+    Asset {
+        name: "SHIP",
+        w: 12,
+        h: 10,
+        planes: 1,
+        frames: &[(33, 0), (43, 0), (53, 0)],
+    },
+    Asset {
+        name: "BULLET",
+        w: 2,
+        h: 2,
+        planes: 1,
+        frames: &[(53, 0)],
+    },
+    Asset {
+        name: "EXPLOSION",
+        w: 11,
+        h: 8,
+        planes: 2,
+        frames: &[(63, 0), (63, 19)],
+    },
+];
+
+// every `0b...` literal is a row, in order
+fn parse_rows(src: &str) -> Vec<u32> {
+    src.lines()
+        .filter_map(|line| {
+            let start = line.find("0b")? + 2;
+            let digits: String = line[start..]
+                .chars()
+                .take_while(|c| *c == '0' || *c == '1' || *c == '_')
+                .filter(|c| *c != '_')
+                .collect();
+            Some(u32::from_str_radix(&digits, 2).expect("bad sprite row"))
+        })
+        .collect()
+}
+
+fn frame_bits(rows: &[u32], asset: &Asset, frame: (u32, u32)) -> Vec<bool> {
+    let mut bits = Vec::new();
+    for plane in 0..asset.planes {
+        for y in 0..asset.h {
+            let row = rows[(frame.0 + plane * asset.h + y) as usize];
+            for x in 0..asset.w {
+                bits.push(row >> (31 - frame.1 - x) & 1 > 0);
+            }
+        }
+    }
+    bits
+}
+
+// msb first
+fn pack(bits: &[bool]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |b, (i, bit)| b | (*bit as u8) << (7 - i))
+        })
+        .collect()
+}
+
+// a byte per run: bit 7 is the value, bits 0..7 the length - 1
+fn rle(bits: &[bool]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bits.len() {
+        let mut run = 1;
+        while i + run < bits.len() && bits[i + run] == bits[i] && run < 128 {
+            run += 1;
+        }
+        out.push((bits[i] as u8) << 7 | (run - 1) as u8);
+        i += run;
+    }
+    out
+}
+
+fn sprites() {
+    let src_path = "src/world/sprites.rs";
+    println!("cargo:rerun-if-changed={}", src_path);
+    println!("cargo:rerun-if-env-changed=SPRITE_REPORT");
+
+    let rows = parse_rows(&fs::read_to_string(src_path).unwrap());
+    let report = env::var("SPRITE_REPORT").is_ok();
+
+    let mut data: Vec<u8> = Vec::new();
+    let mut out = String::new();
+    let mut table = String::new();
+    let mut raw_total = 0;
+
+    for (index, asset) in ASSETS.iter().enumerate() {
+        let mut frames = String::new();
+        let mut raw_rows: Vec<u32> = Vec::new();
+        let start = data.len();
+
+        for frame in asset.frames {
+            let bits = frame_bits(&rows, asset, *frame);
+            let packed = pack(&bits);
+            let runs = rle(&bits);
+            let use_rle = runs.len() < packed.len();
+
+            write!(
+                frames,
+                "PackedFrame {{ offset: {}, rle: {} }}, ",
+                data.len(),
+                use_rle
+            )
+            .unwrap();
+            data.extend(if use_rle { runs } else { packed });
+
+            for y in 0..asset.h * asset.planes {
+                if !raw_rows.contains(&(frame.0 + y)) {
+                    raw_rows.push(frame.0 + y);
+                }
+            }
+        }
+
+        // a u32 per row in the old table
+        let raw = raw_rows.len() * 4;
+        let packed = data.len() - start;
+        raw_total += raw;
+        if report {
+            println!(
+                "cargo:warning=sprite {:<10} {:>4} B -> {:>4} B",
+                asset.name, raw, packed
+            );
+        }
+
+        writeln!(out, "pub const SPR_{}: u8 = {};", asset.name, index).unwrap();
+        writeln!(
+            table,
+            "    SpriteAsset {{ w: {}, h: {}, planes: {}, frames: &[{}] }},",
+            asset.w, asset.h, asset.planes, frames
+        )
+        .unwrap();
+    }
+
+    if report {
+        println!(
+            "cargo:warning=sprites total    {:>4} B -> {:>4} B (table of {} rows was {} B)",
+            raw_total,
+            data.len(),
+            rows.len(),
+            rows.len() * 4
+        );
+    }
+
+    // largest frame unpacked, for the runtime overrides
+    let max_frame = ASSETS
+        .iter()
+        .map(|a| (a.w * a.h * a.planes + 7) / 8)
+        .max()
+        .unwrap();
+    writeln!(out, "pub const MAX_FRAME_BYTES: usize = {};", max_frame).unwrap();
+    writeln!(
+        out,
+        "pub static PACKED_SPRITES: [u8; {}] = {:?};",
+        data.len(),
+        data
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub static SPRITE_ASSETS: [SpriteAsset; {}] = [\n{}];",
+        ASSETS.len(),
+        table
+    )
+    .unwrap();
+
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("sprites.rs");
+    fs::write(dest, out).unwrap();
+}
+
+// midi note and duration in 32nd notes of every note, 0 is a rest.
+// see https://en.wikipedia.org/wiki/Ring_Tone_Text_Transfer_Language
+fn parse_rtttl(src: &str) -> (u32, Vec<(u32, u32)>) {
+    let mut sections = src.splitn(3, ':');
+    let _name = sections.next();
+    let defaults = sections.next().expect("rtttl without defaults");
+    let notes = sections.next().expect("rtttl without notes");
+
+    let (mut duration, mut octave, mut bpm) = (4, 6, 63);
+    for setting in defaults.split(',') {
+        let mut kv = setting.trim().splitn(2, '=');
+        let key = kv.next().unwrap();
+        let value: u32 = kv.next().unwrap().parse().expect("bad rtttl default");
+        match key {
+            "d" => duration = value,
+            "o" => octave = value,
+            "b" => bpm = value,
+            _ => panic!("unknown rtttl default {}", key),
+        }
+    }
+
+    let mut out = Vec::new();
+    for note in notes.split(',') {
+        let note = note.trim();
+        let digits: String = note.chars().take_while(|c| c.is_ascii_digit()).collect();
+        let mut rest = note[digits.len()..].chars().peekable();
+        let d = if digits.is_empty() {
+            duration
+        } else {
+            digits.parse().unwrap()
+        };
+
+        let semitone = match rest.next().expect("empty rtttl note") {
+            'c' => Some(0),
+            'd' => Some(2),
+            'e' => Some(4),
+            'f' => Some(5),
+            'g' => Some(7),
+            'a' => Some(9),
+            'b' | 'h' => Some(11),
+            'p' => None,
+            c => panic!("bad rtttl note {}", c),
+        };
+        let mut sharp = 0;
+        let mut dotted = false;
+        let mut o = octave;
+        for c in rest {
+            match c {
+                '#' => sharp = 1,
+                '.' => dotted = true,
+                '0'..='9' => o = c.to_digit(10).unwrap(),
+                _ => panic!("bad rtttl note {}", note),
+            }
+        }
+
+        let mut len = 32 / d;
+        if dotted {
+            len += len / 2;
+        }
+        let midi = match semitone {
+            Some(s) => 12 * (o + 1) + s + sharp,
+            None => 0,
+        };
+        out.push((midi, len.max(1)));
+    }
+    (bpm, out)
+}
+
+// every line is `NAME = rtttl` or `NAME loop = rtttl`
+fn tunes() {
+    let src_path = "src/world/tunes.rtttl";
+    println!("cargo:rerun-if-changed={}", src_path);
+
+    let mut out = String::new();
+    for line in fs::read_to_string(src_path).unwrap().lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.splitn(2, '=');
+        let mut words = parts.next().unwrap().split_whitespace();
+        let name = words.next().expect("tune without name");
+        let looped = words.next() == Some("loop");
+        let (bpm, notes) = parse_rtttl(parts.next().expect("tune without rtttl").trim());
+
+        let rows: Vec<String> = notes
+            .iter()
+            .map(|(note, len)| format!("Row {{ note: {}, len: {} }}", note, len))
+            .collect();
+        writeln!(
+            out,
+            "pub static TUNE_{}: Song = Song {{ tempo: {}, duty: 20, env: ENV_FLAT, looped: {}, sequence: &[0], patterns: &[&[{}]] }};",
+            name,
+            bpm,
+            looped,
+            rows.join(", ")
+        )
+        .unwrap();
+    }
+
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("tunes.rs");
+    fs::write(dest, out).unwrap();
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    sprites();
+    tunes();
+}
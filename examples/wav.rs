@@ -0,0 +1,54 @@
+// renders a sound effect or a tune the way the buzzer plays it, to a
+// mono 8 bit wav file. host only:
+//
+//   cargo run --example wav --target x86_64-unknown-linux-gnu -- bomb bomb.wav
+//
+// effects: shoot, explode, hit, bomb, levelup. tunes: title, level,
+// gameover. the level tune loops, it is cut after MAX_MS
+use std::env;
+use std::fs;
+use std::process;
+
+use micro_game::world::*;
+
+const MAX_MS: u32 = 10_000;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: wav <sound> <file.wav>");
+        process::exit(2);
+    }
+
+    let mut random = Xorshift128pState::new(1);
+    match args[1].as_str() {
+        "shoot" => play_sfx(Sfx::Shoot, &mut random),
+        "explode" => play_sfx(Sfx::Explode, &mut random),
+        "hit" => play_sfx(Sfx::Hit, &mut random),
+        "bomb" => play_sfx(Sfx::Bomb, &mut random),
+        "levelup" => play_sfx(Sfx::LevelUp, &mut random),
+        "title" => play_music(Tune::Title),
+        "level" => play_music(Tune::Level),
+        "gameover" => play_music(Tune::GameOver),
+        name => {
+            eprintln!("unknown sound {}", name);
+            process::exit(2);
+        }
+    }
+
+    let mut recorder = WavRecorder::new();
+    let mut samples = Vec::new();
+    let mut ms = 0;
+    while (notes_playing() || music_playing()) && ms < MAX_MS {
+        recorder.render(1, |buf| samples.extend_from_slice(buf));
+        ms += 1;
+    }
+
+    let mut wav = wav_header(recorder.samples()).to_vec();
+    wav.extend_from_slice(&samples);
+    if let Err(e) = fs::write(&args[2], &wav) {
+        eprintln!("{}: {}", args[2], e);
+        process::exit(1);
+    }
+    println!("{} ms", ms);
+}
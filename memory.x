@@ -2,7 +2,8 @@
 MEMORY
 {
   /* Flash memory begins at 0x80000000 and has a size of 64kB*/
-  FLASH : ORIGIN = 0x08000000, LENGTH = 64K
+  /* the last 1K page is left for the saved settings, see storage.rs */
+  FLASH : ORIGIN = 0x08000000, LENGTH = 63K
   /* RAM begins at 0x20000000 and has a size of 20kB*/
   RAM : ORIGIN = 0x20000000, LENGTH = 20K
 }
\ No newline at end of file
@@ -0,0 +1,11 @@
+// the game without the board: world, sound, input and display code.
+// the hardware parts only build for the target, everything else also
+// builds on the host, where the tests run:
+//
+//   cargo test --target x86_64-unknown-linux-gnu
+#![no_std]
+
+#[cfg(test)]
+extern crate std;
+
+pub mod world;
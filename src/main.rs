@@ -8,8 +8,6 @@ use cortex_m_rt::entry; // The runtime
 
 use nb::block;
 
-use ssd1306::{prelude::*, Builder};
-
 use embedded_hal::digital::v2::InputPin; // the `set_high/low`function
 
 //use embedded_hal::digital::v2::{InputPin, OutputPin};
@@ -24,40 +22,37 @@ use panic_halt; // When a panic occurs, stop the microcontroller
 //use stm32f1xx_hal::pac::{interrupt, Interrupt};
 //use _micromath::F32Ext;
 
-mod world;
-pub use crate::world::*;
+use micro_game::world::*;
 
+// queue the received bytes, the main loop reads the commands
 #[interrupt]
-fn EXTI9_5() {
-    let led = unsafe { &mut *LED.as_mut_ptr() };
-    let int_pin = unsafe { &mut *INT_PIN.as_mut_ptr() };
+fn USART1() {
     let rxs = unsafe { &mut *RX.as_mut_ptr() };
-    let txs = unsafe { &mut *TX.as_mut_ptr() };
-
-    if int_pin.check_interrupt() {
-        match block!(rxs.read()) {
-            Ok(_received) => {
-                led.toggle().unwrap();
-                unsafe {
-                    BLAST = true;
-                };
 
-                // block!(txs.write(received + 1)).ok();
-                block!(txs.write(66)).ok(); // B
-                block!(txs.write(76)).ok(); // L
-                block!(txs.write(65)).ok(); // A
-                block!(txs.write(83)).ok(); // S
-                block!(txs.write(84)).ok(); // T
-                block!(txs.write(33)).ok(); // !
-                block!(txs.write(10)).ok(); // \n
-            }
-            _ => {}
-        }
-        // if we don't clear this bit, the ISR would trigger indefinitely
-        int_pin.clear_interrupt_pending_bit();
+    // errors are cleared by the read
+    if let Ok(byte) = rxs.read() {
+        RX_QUEUE.push(byte);
     }
 }
 
+// next ms of audio
+#[interrupt]
+fn TIM3() {
+    let audio = unsafe { &mut *AUDIO.as_mut_ptr() };
+    audio.tick();
+}
+
+// a page of the display finished sending, start the next one
+#[cfg(not(feature = "i2c"))]
+#[interrupt]
+fn DMA1_CHANNEL3() {
+    let disp_link = unsafe { &mut *DISP_LINK.as_mut_ptr() };
+    let frames = unsafe { &mut *FRAMES.as_mut_ptr() };
+
+    disp_link.finish();
+    frames.transfer_done(disp_link);
+}
+
 // This marks the entrypoint of our application. The cortex_m_rt creates some
 // startup code before this, but we don't need to worry about this
 #[entry]
@@ -87,8 +82,11 @@ fn main() -> ! {
     let mut a_btn = gpiob.pb1.into_dynamic(&mut gpiob.crl);
     a_btn.make_floating_input(&mut gpiob.crl);
 
-    // let mut b_btn = gpiob.pb11.into_dynamic(&mut gpiob.crh);
-    // b_btn.make_floating_input(&mut gpiob.crh);
+    // pb11 is the i2c data line when the display is on i2c
+    #[cfg(not(feature = "i2c"))]
+    let b_btn = gpiob.pb11.into_floating_input(&mut gpiob.crh);
+    // the joystick switch pulls to ground
+    let stick_btn = gpiob.pb12.into_pull_up_input(&mut gpiob.crh);
 
     // configure analog input
     let mut adc1 = adc::Adc::adc1(dp.ADC1, &mut rcc.apb2, clocks);
@@ -103,18 +101,21 @@ fn main() -> ! {
     // Prepare the alternate function I/O registers
     let mut afio = dp.AFIO.constrain(&mut rcc.apb2);
 
-    // let channels = dp.DMA1.split(&mut rcc.ahb);
-
     let pins = gpiob.pb9.into_alternate_push_pull(&mut gpiob.crh);
 
-    // let PWM = unsafe { &mut *PWM.as_mut_ptr() };
-    let mut pwm = Timer::tim4(dp.TIM4, &clocks, &mut rcc.apb1)
+    let pwm = Timer::tim4(dp.TIM4, &clocks, &mut rcc.apb1)
         .pwm::<Tim4NoRemap, stm32f1xx_hal::pwm::C4, stm32f1xx_hal::gpio::gpiob::PB9<
             stm32f1xx_hal::gpio::Alternate<stm32f1xx_hal::gpio::PushPull>,
         >, stm32f1xx_hal::time::KiloHertz>(pins, &mut afio.mapr, 1.khz());
 
-    let max = pwm.get_max_duty();
-    pwm.set_duty(Channel::C4, max / 10);
+    // notes are played from the TIM3 interrupt
+    let audio_timer = Timer::tim3(dp.TIM3, &clocks, &mut rcc.apb1).start_count_down(AUDIO_HZ.hz());
+    let audio = unsafe { &mut *AUDIO.as_mut_ptr() };
+    *audio = AudioDriver::new(pwm, audio_timer);
+
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::TIM3);
+    }
 
     let tx = gpiob.pb6.into_alternate_push_pull(&mut gpiob.crl);
     let rx = gpiob.pb7;
@@ -134,126 +135,401 @@ fn main() -> ! {
     *rxs = rx;
     *txs = tx;
 
-    // // Set up the usart device. Taks ownership over the USART register and tx/rx pins. The rest of
-    // // the registers are used to enable and configure the device.
-
-    // {
-    //     let led = unsafe { &mut *LED.as_mut_ptr() };
-    //     *led = gpioc.pc13.into_push_pull_output(&mut gpioc.crh);
-
-    //     let int_pin = unsafe { &mut *INT_PIN.as_mut_ptr() };
-    //     *int_pin = gpiob.pb8.into_floating_input(&mut gpiob.crh);
-    //     int_pin.make_interrupt_source(&mut afio);
-    //     int_pin.trigger_on_edge(&dp.EXTI, Edge::RISING_FALLING);
-    //     int_pin.enable_interrupt(&dp.EXTI);
-    // }
-
-    // unsafe {
-    //     pac::NVIC::unmask(pac::Interrupt::EXTI9_5);
-    // }
+    rxs.listen();
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::USART1);
+    }
 
     // Display
 
-    // SPI1
-    let sck = gpioa.pa5.into_alternate_push_pull(&mut gpioa.crl);
-    let miso = gpioa.pa6;
-    // let miso = gpioa.pa4;
-    let mosi = gpioa.pa7.into_alternate_push_pull(&mut gpioa.crl);
-
     let mut rst = gpiob.pb0.into_push_pull_output(&mut gpiob.crl);
-    let dc = gpiob.pb10.into_push_pull_output(&mut gpiob.crh);
+    reset_display(&mut rst, &mut *delay);
+
+    let disp_link = unsafe { &mut *DISP_LINK.as_mut_ptr() };
+
+    // SPI1, the frame is sent by DMA while the next one is drawn
+    #[cfg(not(feature = "i2c"))]
+    {
+        let sck = gpioa.pa5.into_alternate_push_pull(&mut gpioa.crl);
+        let miso = gpioa.pa6;
+        // let miso = gpioa.pa4;
+        let mosi = gpioa.pa7.into_alternate_push_pull(&mut gpioa.crl);
+
+        let dc = gpiob.pb10.into_push_pull_output(&mut gpiob.crh);
+
+        let spi = Spi::spi1(
+            dp.SPI1,
+            (sck, miso, mosi),
+            &mut afio.mapr,
+            Mode {
+                polarity: Polarity::IdleLow,
+                phase: Phase::CaptureOnFirstTransition,
+            },
+            8.mhz(),
+            clocks,
+            &mut rcc.apb2,
+        );
+
+        let channels = dp.DMA1.split(&mut rcc.ahb);
+        *disp_link = SpiDmaLink::new(spi, dc, channels.3);
+
+        unsafe {
+            pac::NVIC::unmask(pac::Interrupt::DMA1_CHANNEL3);
+        }
+    }
 
-    let spi = Spi::spi1(
-        dp.SPI1,
-        (sck, miso, mosi),
-        &mut afio.mapr,
-        Mode {
-            polarity: Polarity::IdleLow,
-            phase: Phase::CaptureOnFirstTransition,
-        },
-        8.mhz(),
-        clocks,
-        &mut rcc.apb2,
-    );
+    // I2C2, every page is sent before `present` returns
+    #[cfg(feature = "i2c")]
+    {
+        let scl = gpiob.pb10.into_alternate_open_drain(&mut gpiob.crh);
+        let sda = gpiob.pb11.into_alternate_open_drain(&mut gpiob.crh);
+
+        let i2c = i2c::BlockingI2c::i2c2(
+            dp.I2C2,
+            (scl, sda),
+            i2c::Mode::Fast {
+                frequency: 400_000.hz(),
+                duty_cycle: i2c::DutyCycle::Ratio2to1,
+            },
+            clocks,
+            &mut rcc.apb1,
+            1000,
+            10,
+            1000,
+            1000,
+        );
+
+        let interface = display_interface_i2c::I2CInterface::new(i2c, 0x3C, 0x40);
+        *disp_link = Oled::new(interface);
+    }
 
-    let interface = display_interface_spi::SPIInterfaceNoCS::new(spi, dc);
-    let mut disp: GraphicsMode<_> = Builder::new().connect(interface).into();
+    PANEL.init(disp_link);
 
-    disp.reset(&mut rst, &mut *delay).unwrap();
-    disp.init().unwrap();
+    // we keep our own framebuffers and only send what changed
+    let frames = unsafe { &mut *FRAMES.as_mut_ptr() };
+    *frames = DoubleBuffer::new();
 
     let mut world = World::new(adc1.read(&mut ch1).unwrap());
 
     let mut input = PlayerInput {
         x_move: 0,
         y_move: 0,
+        x_axis: 0,
+        y_axis: 0,
         a_btn_on: false,
-        a_btn_changed: false,
+        shot: None,
+        bomb: false,
     };
 
     if a_btn.is_high().unwrap() {
         input.a_btn_on = true;
     }
 
+    let mut frame_timer =
+        Timer::tim2(dp.TIM2, &clocks, &mut rcc.apb1).start_count_down(FRAME_HZ.hz());
+    let mut phase = 0u8;
+
+    let mut settings = Settings::load();
+
+    // a full calibration when A is held at boot
+    let mut calibrator = None;
+    if input.a_btn_on {
+        // the stick rests at first, its centre is the mean of a burst
+        let mut xs = [0u16; CENTER_SAMPLES];
+        let mut ys = [0u16; CENTER_SAMPLES];
+        for (x, y) in xs.iter_mut().zip(ys.iter_mut()) {
+            *x = adc1.read(&mut ch1).unwrap();
+            *y = adc2.read(&mut ch2).unwrap();
+        }
+        calibrator = Some(Calibrator::new(
+            settings.calibration,
+            average(&xs),
+            average(&ys),
+        ));
+    }
+    // the console waits for its reply while calibrating
+    let mut calibrate_reply = false;
+    // no stick wired, the board buttons still count
+    let mut stick_detect = StickDetect::new();
+    let mut manager = InputManager::new();
+    let mut pad = PadReader::new();
+    let mut burn_in = BurnIn::new();
+    let mut console = LineReader::new();
+    let mut buttons = Buttons::new();
+    let mut trigger = Trigger::new();
+    let mut sprite_overrides = SpriteOverrides::new();
+    let mut paused = false;
+    // set on the frame after a game over, commands sent during the game
+    // over screen are read then
+    let mut game_over = false;
+    let mut menu = Menu::new();
+
     // prog 0 = game
     // prog 1 = ...
     let prog = 0;
 
-    loop {
-        // read analog control
+    play_music(Tune::Title);
 
-        // yaxis
-        let adc1_data: u16 = adc1.read(&mut ch1).unwrap();
-        if adc1_data < 400 || adc1_data > 2500 {
-            if adc1_data > 2000u16 {
-                input.x_move = -1;
+    loop {
+        let raw_x: u16 = adc1.read(&mut ch1).unwrap();
+        let raw_y: u16 = adc2.read(&mut ch2).unwrap();
+
+        // serial commands
+        let (mut remote_shot, mut remote_bomb) = (None, false);
+        while let Some(byte) = RX_QUEUE.pop() {
+            if pad.push(byte) {
+                continue;
             }
-            if adc1_data < 2000u16 {
-                input.x_move = 1;
+            if let Some(line) = console.push(byte) {
+                let result = match parse(line) {
+                    Ok(Command::Sprite {
+                        sprite,
+                        frame,
+                        data,
+                    }) => {
+                        if data.is_empty() {
+                            sprite_overrides.reset(sprite, frame);
+                            Ok(())
+                        } else {
+                            sprite_overrides.set(sprite, frame, data)
+                        }
+                    }
+                    Ok(Command::Volume(level)) => {
+                        settings.set_volume(level);
+                        settings.save()
+                    }
+                    Ok(Command::Mute(on)) => {
+                        settings.muted = on;
+                        settings.save()
+                    }
+                    Ok(Command::Polyphony(on)) => {
+                        settings.polyphony = on;
+                        settings.save()
+                    }
+                    Ok(Command::DeadZone(percent)) => {
+                        settings.calibration.set_dead_zone(percent);
+                        settings.save()
+                    }
+                    Ok(Command::Brightness(level)) => {
+                        settings.set_brightness(level);
+                        settings.save()
+                    }
+                    Ok(Command::Calibrate) => {
+                        if calibrator.is_some() {
+                            Err("BUSY")
+                        } else {
+                            calibrator = Some(Calibrator::new(settings.calibration, raw_x, raw_y));
+                            calibrate_reply = true;
+                            continue;
+                        }
+                    }
+                    Ok(Command::Move(x, y)) => {
+                        pad.steer(x as i16, y as i16);
+                        Ok(())
+                    }
+                    Ok(Command::Fire) => {
+                        remote_shot = Some(Shot::Normal);
+                        Ok(())
+                    }
+                    Ok(Command::Bomb) => {
+                        remote_bomb = true;
+                        Ok(())
+                    }
+                    Ok(Command::Pause) => {
+                        paused = true;
+                        stop_music();
+                        Ok(())
+                    }
+                    Ok(Command::Start) => {
+                        if paused {
+                            paused = false;
+                            Ok(())
+                        } else if game_over {
+                            // the game over screen already set up the next game
+                            Ok(())
+                        } else {
+                            Err("RUNNING")
+                        }
+                    }
+                    Ok(Command::Score) => {
+                        let values = [world.score, world.level as u32];
+                        reply_values(txs, &values);
+                        continue;
+                    }
+                    Ok(Command::Axes(swap, invert_x, invert_y)) => {
+                        let map = &mut settings.input_map;
+                        map.swap_axes = swap;
+                        map.invert_x = invert_x;
+                        map.invert_y = invert_y;
+                        settings.save()
+                    }
+                    Ok(Command::Map(roles)) => {
+                        settings.input_map.buttons = roles;
+                        settings.save()
+                    }
+                    Ok(Command::Weapon(weapon)) => {
+                        settings.set_weapon(weapon);
+                        settings.save()
+                    }
+                    Ok(Command::Difficulty(level)) => {
+                        settings.set_difficulty(level);
+                        settings.save()
+                    }
+                    Err(reason) => Err(reason),
+                };
+                reply(txs, result);
             }
-        } else {
-            input.x_move = 0;
         }
 
-        // xaxis
-        let adc2_data: u16 = adc2.read(&mut ch2).unwrap();
-        if adc2_data < 400 || adc2_data > 2500 {
-            if adc2_data > 2000u16 {
-                input.y_move = -1;
+        game_over = false;
+
+        // read analog control
+        if stick_detect.due() {
+            let mut xs = [0u16; CENTER_SAMPLES];
+            let mut ys = [0u16; CENTER_SAMPLES];
+            for (x, y) in xs.iter_mut().zip(ys.iter_mut()) {
+                *x = adc1.read(&mut ch1).unwrap();
+                *y = adc2.read(&mut ch2).unwrap();
             }
-            if adc2_data < 2000u16 {
-                input.y_move = 1;
+            if stick_detect.update(&xs, &ys) && calibrator.is_none() {
+                settings.calibration.set_center(average(&xs), average(&ys));
             }
-        } else {
-            input.y_move = 0;
         }
-
-        // handle button
-        if a_btn.is_high().unwrap() {
-            if input.a_btn_on == false {
-                input.a_btn_changed = true
-            } else {
-                input.a_btn_changed = false
+        let stick = match calibrator.as_mut().map(|c| c.sample(raw_x, raw_y)) {
+            // the ship stays put while calibrating
+            Some(None) => (0, 0),
+            Some(Some(result)) => {
+                calibrator = None;
+                let result = result.and_then(|cal| {
+                    stick_detect.wired = true;
+                    settings.calibration = cal;
+                    settings.save()
+                });
+                if calibrate_reply {
+                    calibrate_reply = false;
+                    reply(txs, result);
+                }
+                (0, 0)
             }
-            input.a_btn_on = true;
-        } else {
-            if input.a_btn_on == true {
-                input.a_btn_changed = true
-            } else {
-                input.a_btn_changed = false
+            None if stick_detect.wired => settings.calibration.read(raw_x, raw_y),
+            None => (0, 0),
+        };
+        #[cfg(not(feature = "i2c"))]
+        let b_down = b_btn.is_high().unwrap();
+        #[cfg(feature = "i2c")]
+        let b_down = false;
+        let board = InputFrame {
+            x_axis: stick.0,
+            y_axis: stick.1,
+            buttons: [
+                a_btn.is_high().unwrap(),
+                b_down,
+                stick_btn.is_low().unwrap(),
+            ],
+        };
+        manager.set(Source::Gpio, Some(settings.input_map.apply(board)));
+        manager.set(Source::Pad, pad.tick());
+        let merged = manager.merge();
+        input.x_axis = merged.x_axis;
+        input.y_axis = merged.y_axis;
+        input.x_move = merged.x_axis.signum();
+        input.y_move = merged.y_axis.signum();
+
+        // buttons
+        let weapon = settings.weapon();
+        buttons.set_repeat(Button::A, Trigger::repeat(weapon));
+        buttons.update(merged.buttons);
+        input.shot = remote_shot;
+        input.bomb = remote_bomb;
+        let mut pressed = false;
+        while let Some(event) = buttons.poll() {
+            if let ButtonEvent::Pressed(_) = event {
+                pressed = true;
             }
-            input.a_btn_on = false;
+            if menu.open {
+                match event {
+                    ButtonEvent::Pressed(Button::Stick) => {
+                        menu.open = false;
+                        settings.save().ok();
+                    }
+                    ButtonEvent::Pressed(Button::A) => match menu.select(&mut settings) {
+                        Some(MenuAction::Calibrate) if calibrator.is_none() => {
+                            menu.open = false;
+                            settings.save().ok();
+                            calibrator = Some(Calibrator::new(settings.calibration, raw_x, raw_y));
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+                continue;
+            }
+            if let Some(shot) = trigger.event(weapon, event) {
+                input.shot = Some(shot);
+            }
+            match event {
+                ButtonEvent::Pressed(Button::B) => input.bomb = true,
+                ButtonEvent::Pressed(Button::Stick) => menu.open = true,
+                _ => {}
+            }
+        }
+        input.a_btn_on = buttons.is_down(Button::A);
+        if menu.open {
+            menu.steer(input.x_move, input.y_move, &mut settings);
         }
 
+        // level music once the title or game over tune is over, none
+        // while paused
+        if !music_playing() && !paused {
+            play_music(Tune::Level);
+        }
+
+        let active = input.x_move != 0 || input.y_move != 0 || pressed;
+        burn_in.tick(active);
+
         match prog {
             1 => {}
-            0 => {
+            // the game waits while paused or the screen is blanked
+            0 if !paused && !menu.open && !burn_in.blanked() => {
                 // check world update status
                 match world.tick(input) {
                     // you loose
                     1 => {
-                        pwm.disable(Channel::C4);
+                        let frame = frames.back();
+                        frame.clear();
+                        draw_text(
+                            frame,
+                            DISP_W / 2,
+                            DISP_H / 2 - LINE_H,
+                            b"GAME OVER",
+                            Align::Center,
+                        );
+                        draw_number(
+                            frame,
+                            DISP_W / 2,
+                            DISP_H / 2 + 2,
+                            world.score,
+                            1,
+                            Align::Center,
+                        );
+                        while frames.busy() {}
+                        frames.present(disp_link);
+
+                        // the death sound plays on, the tune waits under
+                        // it or shares the buzzer with polyphony
+                        play_music(Tune::GameOver);
+
+                        world.effects.fade_out(40);
+                        for _ in 0..40 {
+                            delay.delay_ms(50u16);
+                            world.effects.tick(&mut world.random);
+                            while frames.busy() {}
+                            world.effects.apply(disp_link);
+                        }
+
+                        stop_notes();
                         world = World::new(adc1.read(&mut ch1).unwrap());
+                        game_over = true;
                     }
                     // nothing
                     0 => (),
@@ -264,63 +540,46 @@ fn main() -> ! {
             _ => {}
         }
 
-        // update sound state machine
-        if world.sound.active {
-            pwm.enable(Channel::C4);
-
-            if world.sound.counter > world.sound.counter_end {
-                world.sound.counter = 0;
-                world.sound.active = false;
-                pwm.disable(Channel::C4);
-            } else {
-                pwm.set_period(world.sound.freqs[world.sound.counter as usize]);
-                world.sound.counter += 1;
-            }
-        }
+        // settings and screen saver go out with the effects and audio
+        world.effects.brightness = settings.contrast();
+        set_polyphony(settings.polyphony);
+        set_volume(settings.volume_percent());
+        world.effects.dim = burn_in.dimmed();
+        world.effects.blank = burn_in.blanked();
+        world.hud.shift = burn_in.hud_shift();
+        world.difficulty = settings.difficulty;
 
         // clear display
-        for i in 0..64 {
-            for j in 0..128 {
-                disp.set_pixel(j, i, 0);
-            }
-        }
+        let frame = frames.back();
+        frame.clear();
 
         // render
         // let data: u16 = adc1.read(&mut ch0).unwrap();
-        // world.write_number(110, 30, data as u32);
-
-        for i in 0..POOL_SIZE {
-            let entity = world.entities[i];
-            if entity.del != true {
-                for y in 0..entity.h {
-                    let mut bits: u32 = SPRITES[(y + entity.sprite_y as i16) as usize];
-
-                    // starting x bit
-                    bits = bits.rotate_left(entity.sprite_x as u32);
-
-                    for x in 0..entity.w {
-                        bits = bits.rotate_left(1);
-                        let to_paint = bits & 1u32;
-                        if to_paint > 0 {
-                            // avoid print to non existing display coord
-                            let x_pos = x + entity.x;
-                            if x_pos < 0 || x_pos > DISP_W {
-                                continue;
-                            }
-                            let y_pos = y + entity.y;
-                            if y_pos < 0 || y_pos > DISP_H {
-                                continue;
-                            }
-
-                            // print pixel
-                            disp.set_pixel(x_pos as u32, y_pos as u32, 1);
-                        }
-                    }
-                }
-            }
+        // draw_number(frame, 110, 30, data as u32, 1, Align::Right);
+
+        if menu.open {
+            menu.draw(frame, &settings);
+        } else {
+            draw_world(frame, &world, &sprite_overrides, phase);
+            // hud is drawn after the world
+            world.hud.draw(frame, &world);
+        }
+        if paused && !menu.open {
+            draw_text(
+                frame,
+                DISP_W / 2,
+                DISP_H / 2 - LINE_H / 2,
+                b"PAUSED",
+                Align::Center,
+            );
         }
 
-        disp.flush().unwrap();
-        delay.delay_us(1u16);
+        // wait the previous frame to leave before swapping, at a
+        // steady rate so the gray levels don't flicker
+        block!(frame_timer.wait()).ok();
+        while frames.busy() {}
+        world.effects.apply(disp_link);
+        frames.present(disp_link);
+        phase = (phase + 1) % PHASES;
     }
 }
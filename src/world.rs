@@ -1,10 +1,11 @@
-use nb::block;
-
+// the board parts only build for the target, see lib.rs
+#[cfg(target_os = "none")]
 pub use stm32f1xx_hal::{
     adc,
     delay::Delay,
     // dma::Half,
     gpio::*,
+    i2c,
     pac,
     prelude::*,
     pwm::Channel,
@@ -16,53 +17,187 @@ pub use stm32f1xx_hal::{
 
 pub use core::mem::MaybeUninit;
 
-mod sprites;
-pub use self::sprites::*;
+mod packed;
+pub use self::packed::*;
 
 mod seed_rand;
 pub use self::seed_rand::*;
 
-mod sound;
-pub use self::sound::*;
+mod sfx;
+pub use self::sfx::*;
+
+mod audio;
+pub use self::audio::*;
+
+mod music;
+pub use self::music::*;
 
 mod entities;
 pub use self::entities::*;
 
-pub static mut LED: MaybeUninit<stm32f1xx_hal::gpio::gpioc::PC13<Output<PushPull>>> =
-    MaybeUninit::uninit();
-pub static mut INT_PIN: MaybeUninit<stm32f1xx_hal::gpio::gpiob::PB8<Input<Floating>>> =
-    MaybeUninit::uninit();
+mod canvas;
+pub use self::canvas::*;
+
+mod text;
+pub use self::text::*;
+
+mod hud;
+pub use self::hud::*;
+
+mod framebuffer;
+pub use self::framebuffer::*;
+
+mod oled;
+pub use self::oled::*;
+
+mod panel;
+pub use self::panel::*;
+
+mod display_dma;
+pub use self::display_dma::*;
+
+mod render;
+pub use self::render::*;
+
+mod tilemap;
+pub use self::tilemap::*;
+
+mod particles;
+pub use self::particles::*;
+
+mod effects;
+pub use self::effects::*;
+
+mod burn_in;
+pub use self::burn_in::*;
+
+#[cfg(target_os = "none")]
+mod storage;
+#[cfg(target_os = "none")]
+pub use self::storage::*;
+
+mod joystick;
+pub use self::joystick::*;
+
+mod buttons;
+pub use self::buttons::*;
+
+mod input;
+pub use self::input::*;
 
+mod weapons;
+pub use self::weapons::*;
+
+mod menu;
+pub use self::menu::*;
+
+mod settings;
+pub use self::settings::*;
+
+mod queue;
+pub use self::queue::*;
+
+mod console;
+pub use self::console::*;
+
+mod sprite_override;
+pub use self::sprite_override::*;
+
+#[cfg(not(target_os = "none"))]
+mod wav;
+#[cfg(not(target_os = "none"))]
+pub use self::wav::*;
+
+#[cfg(target_os = "none")]
 pub static mut RX: MaybeUninit<stm32f1xx_hal::serial::Rx<stm32f1xx_hal::pac::USART1>> =
     MaybeUninit::uninit();
 
+#[cfg(target_os = "none")]
 pub static mut TX: MaybeUninit<stm32f1xx_hal::serial::Tx<stm32f1xx_hal::pac::USART1>> =
     MaybeUninit::uninit();
 
+#[cfg(target_os = "none")]
 pub static mut DELAY: MaybeUninit<stm32f1xx_hal::delay::Delay> = MaybeUninit::uninit();
 
-// pub static mut PWM: MaybeUninit<Pwm> = MaybeUninit::uninit();
-
-pub static mut BLAST: bool = false;
+#[cfg(all(target_os = "none", not(feature = "i2c")))]
+pub type DispLink = SpiDmaLink<
+    Spi<
+        pac::SPI1,
+        stm32f1xx_hal::spi::Spi1NoRemap,
+        (
+            gpioa::PA5<Alternate<PushPull>>,
+            gpioa::PA6<Input<Floating>>,
+            gpioa::PA7<Alternate<PushPull>>,
+        ),
+    >,
+    gpiob::PB10<Output<PushPull>>,
+>;
+
+#[cfg(all(target_os = "none", feature = "i2c"))]
+pub type DispLink = Oled<
+    display_interface_i2c::I2CInterface<
+        stm32f1xx_hal::i2c::BlockingI2c<
+            pac::I2C2,
+            (
+                gpiob::PB10<Alternate<OpenDrain>>,
+                gpiob::PB11<Alternate<OpenDrain>>,
+            ),
+        >,
+    >,
+>;
+
+// shared with the DMA1_CHANNEL3 interrupt
+#[cfg(target_os = "none")]
+pub static mut DISP_LINK: MaybeUninit<DispLink> = MaybeUninit::uninit();
+#[cfg(target_os = "none")]
+pub static mut FRAMES: MaybeUninit<DoubleBuffer> = MaybeUninit::uninit();
+
+// shared with the TIM3 interrupt
+#[cfg(target_os = "none")]
+pub static mut AUDIO: MaybeUninit<AudioDriver> = MaybeUninit::uninit();
 
 pub const POOL_SIZE: usize = 100;
+pub const SHIP_SPEED: i16 = 2;
+// enemies spawned per 1000 frames at the start, for each difficulty.
+// the score adds to it
+pub const SPAWN_RATE: [u64; DIFFICULTIES as usize] = [60, 100, 160];
 
-pub const DISP_H: i16 = 64i16;
-pub const DISP_W: i16 = 128i16;
+pub const DISP_H: i16 = PANEL.height;
+pub const DISP_W: i16 = PANEL.width;
 
 #[derive(Copy, Clone)]
 pub struct PlayerInput {
+    // -1, 0 or 1
     pub x_move: i16,
     pub y_move: i16,
+    // how far the stick is pushed, -STICK_MAX..=STICK_MAX
+    pub x_axis: i16,
+    pub y_axis: i16,
     pub a_btn_on: bool,
-    pub a_btn_changed: bool,
+    // for one frame
+    pub shot: Option<Shot>,
+    pub bomb: bool,
 }
 
 pub struct World {
     pub entities: [Entity; POOL_SIZE],
     pub random: Xorshift128pState,
     pub score: u32,
-    pub sound: Sound,
+    // for the hud, the game doesn't spend them
+    pub lives: u8,
+    pub level: u8,
+    pub bombs: u8,
+    // zero max means no boss on screen
+    pub boss_hp: u16,
+    pub boss_hp_max: u16,
+    pub hud: Hud,
+    pub background: Background,
+    pub particles: Particles,
+    pub effects: Effects,
+    // ship movement below a pixel, in 1 / STICK_MAX px
+    pub ship_rem: (i16, i16),
+    // index in SPAWN_RATE, from the settings
+    pub difficulty: u8,
 }
 
 impl World {
@@ -70,54 +205,31 @@ impl World {
         return a.x + a.w >= b.x && a.x <= b.x + b.w && a.y + a.h >= b.y && a.y <= b.y + b.h;
     }
 
-    pub fn write_number(&mut self, x: i16, y: i16, mut n: u32) {
-        let number_pool_start = 60;
-
-        // clear digits
-        for i in number_pool_start..70 {
-            let mut entity = self.entities[i];
-            entity.typ = 4;
-            entity.w = 8i16;
-            entity.h = 8i16;
-            entity.y = 15i16;
-            entity.del = true;
-            entity.sprite_x = 0 * 8;
-            entity.sprite_y = 0;
-            self.entities[i] = entity;
-        }
-
-        for i in 0..9 {
-            let m: u32 = n % 10u32;
-            n = n / 10u32;
-
-            let mut entity = self.entities[i + number_pool_start];
-            entity.x = x - i as i16 * 8;
-            entity.y = y;
-            entity.del = false;
-            // get bitflag position of character
-            entity.sprite_x = m as u8 * 8;
-            entity.sprite_y = (m as u8 / 4) * 8;
-            self.entities[i + number_pool_start] = entity;
-
-            if n == 0 {
-                break;
-            }
-        }
-    }
-
     pub fn new(seed: u16) -> World {
+        let hud = Hud::new();
+        let (play_top, play_bottom) = hud.play_area();
+
         let mut world = World {
             entities: [Entity::new(); POOL_SIZE],
             random: Xorshift128pState::new(seed as u64),
             score: 0u32,
-            sound: Sound::new(),
+            lives: 1,
+            level: 1,
+            bombs: 1,
+            boss_hp: 0,
+            boss_hp_max: 0,
+            hud,
+            background: Background::new(play_bottom - play_top),
+            particles: Particles::new(),
+            effects: Effects::new(),
+            ship_rem: (0, 0),
+            difficulty: 1,
         };
 
         // type codes:
         // 0: player
         // 1: enemy
         // 2: bullet
-        // 3: stars
         // Position at array define paint order
 
         // make bullet pool
@@ -129,7 +241,7 @@ impl World {
             entity.x = 70i16;
             entity.y = 40i16;
             entity.del = true;
-            entity.sprite_y = 53;
+            entity.sprite = SPR_BULLET;
             world.entities[i] = entity;
         }
 
@@ -142,26 +254,7 @@ impl World {
             entity.x = 40i16;
             entity.y = 40i16;
             entity.del = true;
-            entity.sprite_y = 24;
-            world.entities[i] = entity;
-        }
-
-        // stars
-        for i in 40..50 {
-            let mut entity = world.entities[i];
-            entity.typ = 3;
-            entity.w = 2i16;
-            entity.h = 1i16;
-            entity.x = 0i16;
-            entity.y = 0i16;
-            entity.del = true;
-            entity.sprite_y = 32;
-            if world.random.gen_min_max(1, 4) == 1 {
-                entity.speed = 2;
-            } else {
-                entity.speed = 1;
-            }
-
+            entity.sprite = SPR_ENEMY;
             world.entities[i] = entity;
         }
 
@@ -172,22 +265,36 @@ impl World {
         entity.h = 10i16;
         entity.x = 20i16;
         entity.y = 0i16;
-        entity.sprite_y = 33u8;
+        entity.sprite = SPR_SHIP;
         entity.del = false;
 
-        entity.y = DISP_H / 2 - entity.h / 2;
+        entity.y = (play_top + play_bottom) / 2 - entity.h / 2;
 
         world.entities[51] = entity;
 
+        // level start
+        world.effects.fade_in(20);
+
         return world;
     }
 
+    pub fn clear_enemies(&mut self) {
+        for i in 0..POOL_SIZE {
+            if self.entities[i].del == false && self.entities[i].typ == 1 {
+                let mut enemy = self.entities[i];
+                enemy.del = true;
+                self.entities[i] = enemy;
+            }
+        }
+    }
+
     pub fn tick(&mut self, input: PlayerInput) -> u16 {
-        let txs = unsafe { &mut *TX.as_mut_ptr() };
-        let delay = unsafe { &mut *DELAY.as_mut_ptr() };
+        let (play_top, play_bottom) = self.hud.play_area();
 
         // spawn new enemies
-        if self.random.gen_min_max(0, 1000) < 100 + (self.score as u64) {
+        if self.random.gen_min_max(0, 1000)
+            < SPAWN_RATE[self.difficulty as usize] + (self.score as u64)
+        {
             // if self.random.gen() < u64::MAX / 500000 + (self.score as u64) / 2 {
             for i in 0..POOL_SIZE {
                 if self.entities[i].del == true && self.entities[i].typ == 1 {
@@ -196,7 +303,7 @@ impl World {
                     enemy.x = DISP_W - enemy.w;
                     enemy.y = self
                         .random
-                        .gen_min_max(0u64, DISP_H as u64 - enemy.h as u64)
+                        .gen_min_max(play_top as u64, play_bottom as u64 - enemy.h as u64)
                         as i16;
                     self.entities[i] = enemy;
                     break;
@@ -204,33 +311,19 @@ impl World {
             }
         }
 
-        unsafe {
-            if BLAST {
-                for i in 0..POOL_SIZE {
-                    if self.entities[i].del == false && self.entities[i].typ == 1 {
-                        let mut enemy = self.entities[i];
-                        enemy.del = true;
-                        self.entities[i] = enemy;
-                    }
-                }
-                BLAST = false;
-            }
-        };
-
-        // stars
-        if self.random.gen() < u64::MAX / 3 {
-            for i in 0..POOL_SIZE {
-                if self.entities[i].del == true && self.entities[i].typ == 3 {
-                    let mut star = self.entities[i];
-                    star.del = false;
-                    star.x = DISP_W - star.w;
-                    star.y = self.random.gen_min_max(0u64, DISP_H as u64 - star.h as u64) as i16;
-                    self.entities[i] = star;
-                    break;
-                }
-            }
+        // smart bomb
+        if input.bomb {
+            self.clear_enemies();
+            self.effects.flash(3);
+            self.effects.shake(6, 1);
+            play_sfx(Sfx::Bomb, &mut self.random);
         }
 
+        // background
+        self.background.scroll();
+        self.particles.tick((play_top, play_bottom));
+        self.effects.tick(&mut self.random);
+
         // update
         for i in 0..POOL_SIZE {
             if self.entities[i].del != true {
@@ -242,7 +335,6 @@ impl World {
                     // remove score if enemy pass
                     if entity.typ == 1 && self.score > 0 {
                         self.score -= 1;
-                        self.write_number(110, 1, self.score);
                         entity.del = true;
                     }
 
@@ -262,22 +354,38 @@ impl World {
                     for j in 0..POOL_SIZE {
                         if self.entities[j].del == false && self.entities[j].typ == 1 {
                             let mut enemy = self.entities[j];
-                            if self.has_collision(entity, enemy) {
+                            // a charged shot passes over the enemies it dissolved
+                            let dissolving = entity.state > 0 && enemy.state >= 40;
+                            if !dissolving && self.has_collision(entity, enemy) {
                                 // enemy.del = true;
 
                                 // set to disolve state
                                 enemy.state = 40;
-                                entity.del = true;
+                                // a charged shot keeps going
+                                entity.del = entity.state == 0;
+
+                                self.particles.emit(
+                                    &mut self.random,
+                                    enemy.x + enemy.w / 2,
+                                    enemy.y + enemy.h / 2,
+                                    &EMIT_EXPLOSION,
+                                );
+                                self.particles.emit(
+                                    &mut self.random,
+                                    entity.x + entity.w,
+                                    entity.y,
+                                    &EMIT_IMPACT,
+                                );
                                 // update score
                                 self.score += 1;
-
-                                self.sound.counter = 0;
-                                self.sound.counter_end = 2;
-                                self.sound.active = true;
-
-                                self.write_number(110, 1, self.score);
-                                block!(txs.write(self.score as u8 + 48u8)).ok();
-                                block!(txs.write(10)).ok();
+                                let level = 1 + (self.score / 25) as u8;
+                                if level != self.level {
+                                    self.level = level;
+                                    self.effects.flash(2);
+                                    play_sfx(Sfx::LevelUp, &mut self.random);
+                                } else {
+                                    play_sfx(Sfx::Explode, &mut self.random);
+                                }
                             }
                             self.entities[j] = enemy;
                         }
@@ -287,32 +395,60 @@ impl World {
 
                 // player updates
                 if entity.typ == 0 {
+                    let mut hit = false;
                     for j in 0..POOL_SIZE {
                         if self.entities[j].del == false && self.entities[j].typ == 1 {
                             let enemy = self.entities[j];
                             if self.has_collision(entity, enemy) {
-                                self.score = 0;
-                                self.sound.counter = 3;
-                                self.sound.counter_end = 4;
-                                self.sound.active = true;
-
-                                delay.delay_ms(2000u16);
-                                return 1u16;
+                                hit = true;
+                                break;
                             }
                         }
                     }
 
-                    entity.x = entity.x + input.x_move * 2;
-                    entity.y = entity.y + input.y_move * 2;
+                    if hit {
+                        play_sfx(Sfx::Hit, &mut self.random);
+
+                        self.particles.emit(
+                            &mut self.random,
+                            entity.x + entity.w / 2,
+                            entity.y + entity.h / 2,
+                            &EMIT_EXPLOSION,
+                        );
+
+                        self.effects.shake(12, 2);
+                        self.effects.flash(2);
+
+                        return 1u16;
+                    }
+
+                    // full tilt is SHIP_SPEED px a frame
+                    let rem = &mut self.ship_rem;
+                    rem.0 += input.x_axis * SHIP_SPEED;
+                    rem.1 += input.y_axis * SHIP_SPEED;
+                    entity.x += rem.0 / STICK_MAX;
+                    entity.y += rem.1 / STICK_MAX;
+                    rem.0 %= STICK_MAX;
+                    rem.1 %= STICK_MAX;
+
+                    // engine exhaust while speeding forward
+                    if input.x_move > 0 {
+                        self.particles.emit(
+                            &mut self.random,
+                            entity.x,
+                            entity.y + entity.h / 2,
+                            &EMIT_THRUST,
+                        );
+                    }
 
                     if input.y_move > 0 {
-                        entity.sprite_y = 43;
+                        entity.frame = 1;
                     }
                     if input.y_move < 0 {
-                        entity.sprite_y = 53;
+                        entity.frame = 2;
                     }
                     if input.y_move == 0 {
-                        entity.sprite_y = 33;
+                        entity.frame = 0;
                     }
 
                     // dont allow player move outside canvas
@@ -322,15 +458,15 @@ impl World {
                     if entity.x + entity.w > DISP_W {
                         entity.x = DISP_W - entity.w;
                     }
-                    if entity.y < 0 {
-                        entity.y = 0;
+                    if entity.y < play_top {
+                        entity.y = play_top;
                     }
-                    if entity.y + entity.h > DISP_H {
-                        entity.y = DISP_H - entity.h;
+                    if entity.y + entity.h > play_bottom {
+                        entity.y = play_bottom - entity.h;
                     }
 
-                    // on key press, shoot a bullet
-                    if input.a_btn_on && input.a_btn_changed {
+                    // shoot a bullet
+                    if let Some(shot) = input.shot {
                         // find a deleted bullet on pool
                         for j in 0..POOL_SIZE {
                             if self.entities[j].del == true && self.entities[j].typ == 2 {
@@ -338,17 +474,10 @@ impl World {
                                 bullet.del = false;
                                 bullet.x = entity.x + entity.w + 1;
                                 bullet.y = entity.y + entity.h / 2 - bullet.h / 2;
+                                bullet.state = (shot == Shot::Charged) as u8;
                                 self.entities[j] = bullet;
 
-                                self.sound.freqs[3 as usize] =
-                                    Hertz(self.random.gen_min_max(3400, 3700) as u32);
-
-                                self.sound.freqs[4 as usize] =
-                                    Hertz(self.random.gen_min_max(2800, 3000) as u32);
-
-                                self.sound.counter = 3;
-                                self.sound.counter_end = 4;
-                                self.sound.active = true;
+                                play_sfx(Sfx::Shoot, &mut self.random);
 
                                 break;
                             }
@@ -370,33 +499,25 @@ impl World {
                             entity.state = 0;
                         }
                         if entity.state < 20 {
-                            entity.sprite_x = 19;
+                            entity.frame = 1;
                         }
                         if entity.state < 10 {
-                            entity.sprite_x = 0;
+                            entity.frame = 0;
                         }
                     } else {
                         // state > 40 enemy is dissolving
                         if entity.state < 42 {
-                            // set "dissolve" sprite position
-                            entity.sprite_y = 63;
+                            // set "dissolve" sprite, it is gray
+                            entity.sprite = SPR_EXPLOSION;
                         } else {
                             // reset entity
                             entity.state = 0;
                             entity.del = true;
-                            entity.sprite_y = 24;
+                            entity.sprite = SPR_ENEMY;
                         }
                     }
                     self.entities[i] = entity;
                 }
-
-                // stars update
-                if entity.typ == 3 {
-                    entity.state = entity.state.wrapping_add(1);
-                    entity.sprite_x = self.random.gen_min_max(0, 30) as u8;
-                    entity.x -= 3 + entity.speed as i16;
-                    self.entities[i] = entity;
-                }
             }
         }
         return 0u16;
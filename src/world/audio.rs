@@ -0,0 +1,297 @@
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+#[cfg(target_os = "none")]
+use stm32f1xx_hal::pwm::{Pwm, C4};
+#[cfg(target_os = "none")]
+use stm32f1xx_hal::timer::{CountDownTimer, Event};
+
+use super::*;
+
+// the driver runs once per ms, so durations don't depend on the frame rate
+pub const AUDIO_HZ: u32 = 1000;
+pub const NOTES: usize = 32;
+
+// how the duty of a note moves while it plays: up to full in `attack`
+// ms, then down to `sustain` percent in `decay` ms
+#[derive(Copy, Clone, PartialEq)]
+pub struct Envelope {
+    pub attack: u8,
+    pub decay: u8,
+    pub sustain: u8,
+}
+
+pub const ENV_FLAT: Envelope = Envelope {
+    attack: 0,
+    decay: 0,
+    sustain: 100,
+};
+pub const ENV_PLUCK: Envelope = Envelope {
+    attack: 0,
+    decay: 60,
+    sustain: 30,
+};
+pub const ENV_SWELL: Envelope = Envelope {
+    attack: 15,
+    decay: 0,
+    sustain: 100,
+};
+
+impl Envelope {
+    // percent of the note duty `ms` into the note
+    pub fn level(&self, ms: u16) -> u8 {
+        let (attack, decay) = (self.attack as u16, self.decay as u16);
+        if ms < attack {
+            return (ms * 100 / attack) as u8;
+        }
+        let ms = ms - attack;
+        if ms < decay {
+            let drop = (100 - self.sustain.min(100)) as u16;
+            return (100 - drop * ms / decay) as u8;
+        }
+        self.sustain
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct Note {
+    // 0 is a rest
+    pub freq: u16,
+    pub ms: u16,
+    // percent of the period the pin is high, 50 is the loudest
+    pub duty: u8,
+    pub env: Envelope,
+}
+
+impl Note {
+    pub const fn new(freq: u16, ms: u16, duty: u8) -> Note {
+        Note {
+            freq,
+            ms,
+            duty,
+            env: ENV_FLAT,
+        }
+    }
+
+    pub const fn rest(ms: u16) -> Note {
+        Note::new(0, ms, 0)
+    }
+
+    pub const fn with_env(self, env: Envelope) -> Note {
+        Note { env, ..self }
+    }
+}
+
+// effect notes. game code pushes, the TIM3 interrupt pops
+pub static NOTE_QUEUE: Queue<Note, NOTES> = Queue::new(Note::rest(0));
+// the current effect note must stop now
+static CUT: AtomicBool = AtomicBool::new(false);
+static PLAYING: AtomicBool = AtomicBool::new(false);
+// effects and music take turns every SLICE_MS instead of the music
+// waiting for the effect to end
+static POLYPHONY: AtomicBool = AtomicBool::new(false);
+pub const SLICE_MS: u16 = 8;
+// percent of every note duty, 0 is muted
+static VOLUME: AtomicU8 = AtomicU8::new(100);
+
+// false when the queue is full
+pub fn play_note(note: Note) -> bool {
+    NOTE_QUEUE.push(note)
+}
+
+// silence, dropping the queued notes. notes played after this call
+// are kept
+pub fn stop_notes() {
+    // the interrupt is the other consumer, it can't run in here
+    #[cfg(target_os = "none")]
+    cortex_m::interrupt::free(|_| while NOTE_QUEUE.pop().is_some() {});
+    // the host renders in the same thread, see wav.rs
+    #[cfg(not(target_os = "none"))]
+    while NOTE_QUEUE.pop().is_some() {}
+    CUT.store(true, Ordering::Release);
+}
+
+pub fn notes_playing() -> bool {
+    PLAYING.load(Ordering::Acquire) || !NOTE_QUEUE.is_empty()
+}
+
+pub fn set_polyphony(on: bool) {
+    POLYPHONY.store(on, Ordering::Release);
+}
+
+pub fn set_volume(percent: u8) {
+    VOLUME.store(percent.min(100), Ordering::Release);
+}
+
+// a note being played and what is left of it
+#[derive(Copy, Clone)]
+struct Voice {
+    note: Option<Note>,
+    left: u16,
+}
+
+impl Voice {
+    const fn idle() -> Voice {
+        Voice {
+            note: None,
+            left: 0,
+        }
+    }
+
+    // one ms, `next` is asked for a note when the current one is over
+    fn step<F: FnOnce() -> Option<Note>>(&mut self, next: F) {
+        if self.left > 1 {
+            self.left -= 1;
+            return;
+        }
+        self.note = next();
+        self.left = self.note.map_or(0, |note| note.ms);
+    }
+
+    fn sounding(&self) -> Option<Note> {
+        self.note.filter(|note| note.freq > 0 && note.duty > 0)
+    }
+
+    // ms since the note started, for its envelope
+    fn elapsed(&self) -> u16 {
+        self.note
+            .map_or(0, |note| note.ms.saturating_sub(self.left))
+    }
+}
+
+// what should come out of the buzzer each ms: the effect and music
+// voices and how they share it. no hardware in here, so the host can
+// render it too, see wav.rs
+pub struct Mixer {
+    effect: Voice,
+    music_voice: Voice,
+    music: Tracker,
+    // ms since start, for the polyphony slices
+    clock: u16,
+}
+
+impl Mixer {
+    pub fn new() -> Mixer {
+        Mixer {
+            effect: Voice::idle(),
+            music_voice: Voice::idle(),
+            music: Tracker::new(),
+            clock: 0,
+        }
+    }
+
+    // one ms. the note to play and its duty in per mille, after the
+    // envelope and the volume
+    pub fn tick(&mut self) -> Option<(Note, u32)> {
+        self.clock = self.clock.wrapping_add(1);
+        self.music.poll();
+
+        if CUT.swap(false, Ordering::AcqRel) {
+            self.effect = Voice::idle();
+        }
+        self.effect.step(|| NOTE_QUEUE.pop());
+        if self.effect.note.is_none() {
+            SFX_PRIORITY.store(0, Ordering::Release);
+        }
+
+        // without polyphony the music holds its place under an effect
+        let polyphony = POLYPHONY.load(Ordering::Acquire);
+        if polyphony || self.effect.note.is_none() {
+            let music = &mut self.music;
+            self.music_voice.step(|| music.next());
+        }
+
+        let effect = self
+            .effect
+            .sounding()
+            .map(|note| (note, self.effect.elapsed()));
+        let music = self
+            .music_voice
+            .sounding()
+            .map(|note| (note, self.music_voice.elapsed()));
+        let output = match (effect, music) {
+            (Some(effect), Some(music)) if polyphony => {
+                if self.clock / SLICE_MS % 2 == 0 {
+                    Some(effect)
+                } else {
+                    Some(music)
+                }
+            }
+            (Some(effect), _) => Some(effect),
+            // a rest in the effect silences the music too
+            (None, music) if polyphony || self.effect.note.is_none() => music,
+            _ => None,
+        };
+
+        PLAYING.store(
+            self.effect.note.is_some() || self.music_voice.note.is_some(),
+            Ordering::Release,
+        );
+        output.map(|(note, elapsed)| {
+            let volume = VOLUME.load(Ordering::Acquire) as u32;
+            let env = note.env.level(elapsed) as u32;
+            (note, note.duty.min(100) as u32 * env * volume / 1000)
+        })
+    }
+}
+
+#[cfg(target_os = "none")]
+pub type Buzzer = Pwm<pac::TIM4, Tim4NoRemap, C4, gpiob::PB9<Alternate<PushPull>>>;
+
+// owned by the TIM3 interrupt once started. effects pause the music,
+// or share the buzzer with it when polyphony is on
+#[cfg(target_os = "none")]
+pub struct AudioDriver {
+    buzzer: Buzzer,
+    timer: CountDownTimer<pac::TIM3>,
+    mixer: Mixer,
+    // what the buzzer is playing, and its duty in per mille
+    output: Option<Note>,
+    duty: u32,
+}
+
+#[cfg(target_os = "none")]
+impl AudioDriver {
+    pub fn new(mut buzzer: Buzzer, mut timer: CountDownTimer<pac::TIM3>) -> AudioDriver {
+        buzzer.disable(Channel::C4);
+        timer.listen(Event::Update);
+        AudioDriver {
+            buzzer,
+            timer,
+            mixer: Mixer::new(),
+            output: None,
+            duty: 0,
+        }
+    }
+
+    // call from the TIM3 interrupt
+    pub fn tick(&mut self) {
+        self.timer.clear_update_interrupt_flag();
+        let output = self.mixer.tick();
+        self.play(output);
+    }
+
+    fn play(&mut self, output: Option<(Note, u32)>) {
+        let note = output.map(|(note, _)| note);
+        let duty = output.map_or(0, |(_, duty)| duty);
+        if note != self.output {
+            self.output = note;
+            if let Some(note) = note {
+                // the period sets the max duty, so the duty is set again
+                self.buzzer.set_period(Hertz(note.freq as u32));
+                self.duty = u32::MAX;
+            }
+        }
+        if duty == self.duty {
+            return;
+        }
+        self.duty = duty;
+        if duty == 0 {
+            self.buzzer.disable(Channel::C4);
+        } else {
+            let max = self.buzzer.get_max_duty() as u32;
+            self.buzzer
+                .set_duty(Channel::C4, (max * duty / 1000) as u16);
+            self.buzzer.enable(Channel::C4);
+        }
+    }
+}
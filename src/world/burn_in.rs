@@ -0,0 +1,56 @@
+use super::*;
+
+// oled pixels age with use, a static hud ends up etched on the panel.
+// times are in frames
+pub const DIM_AFTER: u16 = 30 * FRAME_HZ as u16;
+pub const BLANK_AFTER: u16 = 90 * FRAME_HZ as u16;
+// the hud moves to the next orbit position this often
+pub const SHIFT_EVERY: u16 = 20 * FRAME_HZ as u16;
+
+// small square walked by the hud, it fits the spare row of the band
+// and the spare column right of the score
+const ORBIT: [(i16, i16); 4] = [(0, 0), (1, 0), (1, 1), (0, 1)];
+
+// lives outside the world, so it keeps counting across games
+pub struct BurnIn {
+    idle: u16,
+    shift_timer: u16,
+    orbit: usize,
+}
+
+impl BurnIn {
+    pub fn new() -> BurnIn {
+        BurnIn {
+            idle: 0,
+            shift_timer: 0,
+            orbit: 0,
+        }
+    }
+
+    // once per frame, `active` when the player touched any control
+    pub fn tick(&mut self, active: bool) {
+        if active {
+            self.idle = 0;
+        } else if self.idle < BLANK_AFTER {
+            self.idle += 1;
+        }
+
+        self.shift_timer += 1;
+        if self.shift_timer >= SHIFT_EVERY {
+            self.shift_timer = 0;
+            self.orbit = (self.orbit + 1) % ORBIT.len();
+        }
+    }
+
+    pub fn dimmed(&self) -> bool {
+        self.idle >= DIM_AFTER
+    }
+
+    pub fn blanked(&self) -> bool {
+        self.idle >= BLANK_AFTER
+    }
+
+    pub fn hud_shift(&self) -> (i16, i16) {
+        ORBIT[self.orbit]
+    }
+}
@@ -0,0 +1,139 @@
+use super::*;
+
+// polled once per frame, so times are in frames
+// samples a new level must hold before it counts, bounces are shorter
+pub const DEBOUNCE: u8 = 2;
+pub const LONG_PRESS: u16 = FRAME_HZ as u16 / 2;
+// a press this soon after a short tap is a double tap
+pub const DOUBLE_TAP: u16 = FRAME_HZ as u16 * 3 / 10;
+pub const BUTTON_EVENTS: usize = 16;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Button {
+    A,
+    B,
+    // the joystick switch
+    Stick,
+}
+
+pub const BUTTON_COUNT: usize = 3;
+pub const BUTTONS: [Button; BUTTON_COUNT] = [Button::A, Button::B, Button::Stick];
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ButtonEvent {
+    Pressed(Button),
+    Released(Button),
+    // every frame the button stays down, with the frames so far
+    Held(Button, u16),
+    // once per press, after LONG_PRESS
+    LongPress(Button),
+    // on the second press, after its Pressed
+    DoubleTap(Button),
+    // key repeat while held, see Repeat
+    Repeat(Button),
+}
+
+// key repeat: after `delay` frames held, a Repeat event every `every`
+// frames
+#[derive(Copy, Clone, PartialEq)]
+pub struct Repeat {
+    pub delay: u16,
+    pub every: u16,
+}
+
+#[derive(Copy, Clone)]
+struct ButtonState {
+    // debounced level
+    down: bool,
+    // samples the raw level has differed from `down`
+    bounce: u8,
+    // frames since the last press or release
+    frames: u16,
+    // the last press was a short tap, so a double tap can follow
+    tapped: bool,
+}
+
+impl ButtonState {
+    const fn new() -> ButtonState {
+        ButtonState {
+            down: false,
+            bounce: 0,
+            frames: u16::MAX,
+            tapped: false,
+        }
+    }
+}
+
+pub struct Buttons {
+    states: [ButtonState; BUTTON_COUNT],
+    repeat: [Option<Repeat>; BUTTON_COUNT],
+    events: Queue<ButtonEvent, BUTTON_EVENTS>,
+}
+
+impl Buttons {
+    pub fn new() -> Buttons {
+        Buttons {
+            states: [ButtonState::new(); BUTTON_COUNT],
+            repeat: [None; BUTTON_COUNT],
+            events: Queue::new(ButtonEvent::Released(Button::A)),
+        }
+    }
+
+    // once per frame with the raw levels in the order of Button, true
+    // is pressed. events past the queue size are dropped
+    pub fn update(&mut self, raw: [bool; BUTTON_COUNT]) {
+        for (i, &button) in BUTTONS.iter().enumerate() {
+            let state = &mut self.states[i];
+            state.frames = state.frames.saturating_add(1);
+
+            if raw[i] == state.down {
+                state.bounce = 0;
+            } else {
+                state.bounce += 1;
+                if state.bounce >= DEBOUNCE {
+                    state.bounce = 0;
+                    state.down = raw[i];
+                    let quick = state.frames <= DOUBLE_TAP;
+                    state.frames = 0;
+                    if state.down {
+                        self.events.push(ButtonEvent::Pressed(button));
+                        if quick && state.tapped {
+                            state.tapped = false;
+                            self.events.push(ButtonEvent::DoubleTap(button));
+                        }
+                    } else {
+                        state.tapped = quick;
+                        self.events.push(ButtonEvent::Released(button));
+                    }
+                    continue;
+                }
+            }
+
+            if state.down {
+                self.events.push(ButtonEvent::Held(button, state.frames));
+                if state.frames == LONG_PRESS {
+                    self.events.push(ButtonEvent::LongPress(button));
+                }
+                if let Some(repeat) = self.repeat[i] {
+                    let frames = state.frames;
+                    if frames >= repeat.delay && (frames - repeat.delay) % repeat.every.max(1) == 0
+                    {
+                        self.events.push(ButtonEvent::Repeat(button));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn set_repeat(&mut self, button: Button, repeat: Option<Repeat>) {
+        self.repeat[button as usize] = repeat;
+    }
+
+    pub fn poll(&mut self) -> Option<ButtonEvent> {
+        self.events.pop()
+    }
+
+    pub fn is_down(&self, button: Button) -> bool {
+        self.states[button as usize].down
+    }
+}
@@ -0,0 +1,96 @@
+// anything we can paint pixels on. only set_pixel is required, the
+// other primitives are built on it and can be overridden when the
+// target has a faster way
+pub trait Canvas {
+    fn set_pixel(&mut self, x: i16, y: i16, on: bool);
+
+    fn fill_rect(&mut self, x: i16, y: i16, w: i16, h: i16, on: bool) {
+        for j in y..y + h {
+            for i in x..x + w {
+                self.set_pixel(i, j, on);
+            }
+        }
+    }
+
+    fn hline(&mut self, x: i16, y: i16, w: i16, on: bool) {
+        self.fill_rect(x, y, w, 1, on);
+    }
+
+    fn vline(&mut self, x: i16, y: i16, h: i16, on: bool) {
+        self.fill_rect(x, y, 1, h, on);
+    }
+
+    fn rect(&mut self, x: i16, y: i16, w: i16, h: i16, on: bool) {
+        if w <= 0 || h <= 0 {
+            return;
+        }
+        self.hline(x, y, w, on);
+        self.hline(x, y + h - 1, w, on);
+        self.vline(x, y + 1, h - 2, on);
+        self.vline(x + w - 1, y + 1, h - 2, on);
+    }
+
+    // bresenham
+    fn line(&mut self, x0: i16, y0: i16, x1: i16, y1: i16, on: bool) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+
+        loop {
+            self.set_pixel(x, y, on);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = err * 2;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    // midpoint circle outline
+    fn circle(&mut self, cx: i16, cy: i16, r: i16, on: bool) {
+        let (mut x, mut y) = (r, 0);
+        let mut err = 1 - r;
+
+        while x >= y {
+            self.set_pixel(cx + x, cy + y, on);
+            self.set_pixel(cx + y, cy + x, on);
+            self.set_pixel(cx - y, cy + x, on);
+            self.set_pixel(cx - x, cy + y, on);
+            self.set_pixel(cx - x, cy - y, on);
+            self.set_pixel(cx - y, cy - x, on);
+            self.set_pixel(cx + y, cy - x, on);
+            self.set_pixel(cx + x, cy - y, on);
+
+            y += 1;
+            if err < 0 {
+                err += 2 * y + 1;
+            } else {
+                x -= 1;
+                err += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    // bitmap in the display format: w columns per 8 row page, bit 0 on
+    // top. only set bits are painted
+    fn blit(&mut self, x: i16, y: i16, w: i16, h: i16, data: &[u8]) {
+        for row in 0..h {
+            for col in 0..w {
+                let byte = data[((row / 8) * w + col) as usize];
+                if byte >> (row % 8) & 1 > 0 {
+                    self.set_pixel(x + col, y + row, true);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+// text commands over the serial port, usually a phone on the hc-05,
+// one per line. every line gets "OK" or "ERR <reason>" back
+//
+//   MOVE <x> <y>                 steer, -100..100 each way, positive is
+//                                right and down. lasts a second, resend
+//                                to keep moving, MOVE 0 0 stops
+//   FIRE                         one shot
+//   BOMB                         smart bomb
+//   PAUSE                        freeze the game and stop the music
+//   START                        resume a paused game, ERR RUNNING while
+//                                one is being played
+//   SCORE                        "OK <score> <level>"
+//   DIFF <0|1|2>                 easy, normal, hard. saved
+//   AXES <swap> <inv x> <inv y>  board stick mounting, 0 or 1 each. saved
+//   MAP <a> <b> <stick>          what each board button does, 0 fire,
+//                                1 bomb, 2 menu. one must be menu. saved
+//   WEAPON <0|1|2>               blaster: a shot a press, rapid: autofire,
+//                                cannon: hold to charge. saved
+//
+//   SPR <sprite> <frame> <hex>   draw this frame instead of the flash one,
+//                                hex is the plain packed format, see packed.rs
+//   SPR <sprite> <frame>         back to the flash frame
+//   VOL <0-4>                    volume level, 0 is the quietest. saved
+//   MUTE <0|1>                   saved
+//   POLY <0|1>                   music keeps playing under the sound
+//                                effects. saved
+//   CAL                          joystick calibration: leave the stick
+//                                centred, then move it to all its edges.
+//                                replies when done, after a few seconds
+//   DEAD <0-90>                  joystick dead zone in percent. saved
+//   BRIGHT <0-4>                 display brightness, 2 is the default.
+//                                saved
+
+use embedded_hal::serial::Write;
+use nb::block;
+
+use super::*;
+
+pub const LINE_MAX: usize = 64;
+
+pub enum Command<'a> {
+    Sprite {
+        sprite: u8,
+        frame: u8,
+        // empty to reset
+        data: &'a [u8],
+    },
+    Volume(u8),
+    Mute(bool),
+    Polyphony(bool),
+    Calibrate,
+    DeadZone(u8),
+    Brightness(u8),
+    Move(i8, i8),
+    Fire,
+    Bomb,
+    Pause,
+    Start,
+    Score,
+    Difficulty(u8),
+    Weapon(u8),
+    Axes(bool, bool, bool),
+    Map([Button; BUTTON_COUNT]),
+}
+
+// collects bytes until a line is complete. the end of a too long
+// line is dropped, so it fails to parse
+pub struct LineReader {
+    buf: [u8; LINE_MAX],
+    len: usize,
+}
+
+impl LineReader {
+    pub fn new() -> LineReader {
+        LineReader {
+            buf: [0; LINE_MAX],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, byte: u8) -> Option<&mut [u8]> {
+        match byte {
+            b'\n' => {
+                let len = self.len;
+                self.len = 0;
+                Some(&mut self.buf[..len])
+            }
+            b'\r' => None,
+            _ => {
+                if self.len < LINE_MAX {
+                    self.buf[self.len] = byte;
+                    self.len += 1;
+                }
+                None
+            }
+        }
+    }
+}
+
+fn parse_u8(word: &[u8]) -> Option<u8> {
+    if word.is_empty() || word.len() > 3 {
+        return None;
+    }
+    let mut n: u16 = 0;
+    for c in word {
+        if !c.is_ascii_digit() {
+            return None;
+        }
+        n = n * 10 + (c - b'0') as u16;
+    }
+    if n > u8::MAX as u16 {
+        return None;
+    }
+    Some(n as u8)
+}
+
+// -100..=100, enough for the stick
+fn parse_axis(word: &[u8]) -> Option<i8> {
+    let (negative, digits) = match word.split_first() {
+        Some((b'-', digits)) => (true, digits),
+        _ => (false, word),
+    };
+    let n = parse_u8(digits)?;
+    if n > 100 {
+        return None;
+    }
+    Some(if negative { -(n as i8) } else { n as i8 })
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+// decodes in place, the bytes take the first half of the word
+fn decode_hex(word: &mut [u8]) -> Option<&[u8]> {
+    if word.len() % 2 != 0 {
+        return None;
+    }
+    for i in 0..word.len() / 2 {
+        let hi = hex_digit(word[i * 2])?;
+        let lo = hex_digit(word[i * 2 + 1])?;
+        word[i] = hi << 4 | lo;
+    }
+    Some(&word[..word.len() / 2])
+}
+
+// (start, end) of the space separated words
+fn split(line: &[u8], words: &mut [(usize, usize)]) -> usize {
+    let mut count = 0;
+    let mut i = 0;
+    while i < line.len() {
+        if line[i] == b' ' {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < line.len() && line[i] != b' ' {
+            i += 1;
+        }
+        if count == words.len() {
+            // too many words, report it as one more
+            return count + 1;
+        }
+        words[count] = (start, i);
+        count += 1;
+    }
+    count
+}
+
+pub fn parse(line: &mut [u8]) -> Result<Command<'_>, &'static str> {
+    let mut words = [(0, 0); 5];
+    let count = split(line, &mut words);
+    if count == 0 {
+        return Err("EMPTY");
+    }
+    let word = |i: usize| &line[words[i].0..words[i].1];
+
+    match word(0) {
+        b"SPR" => {
+            if count < 3 || count > 4 {
+                return Err("ARGS");
+            }
+            let sprite = parse_u8(word(1)).ok_or("NUMBER")?;
+            let frame = parse_u8(word(2)).ok_or("NUMBER")?;
+            let data = if count == 4 {
+                let (start, end) = words[3];
+                decode_hex(&mut line[start..end]).ok_or("HEX")?
+            } else {
+                &[]
+            };
+            Ok(Command::Sprite {
+                sprite,
+                frame,
+                data,
+            })
+        }
+        b"VOL" => {
+            if count != 2 {
+                return Err("ARGS");
+            }
+            match parse_u8(word(1)) {
+                Some(level) if (level as usize) < VOLUME_LEVELS.len() => Ok(Command::Volume(level)),
+                _ => Err("NUMBER"),
+            }
+        }
+        b"MUTE" => {
+            if count != 2 {
+                return Err("ARGS");
+            }
+            match parse_u8(word(1)) {
+                Some(0) => Ok(Command::Mute(false)),
+                Some(1) => Ok(Command::Mute(true)),
+                _ => Err("NUMBER"),
+            }
+        }
+        b"POLY" => {
+            if count != 2 {
+                return Err("ARGS");
+            }
+            match parse_u8(word(1)) {
+                Some(0) => Ok(Command::Polyphony(false)),
+                Some(1) => Ok(Command::Polyphony(true)),
+                _ => Err("NUMBER"),
+            }
+        }
+        b"CAL" => {
+            if count != 1 {
+                return Err("ARGS");
+            }
+            Ok(Command::Calibrate)
+        }
+        b"DEAD" => {
+            if count != 2 {
+                return Err("ARGS");
+            }
+            match parse_u8(word(1)) {
+                Some(percent) if percent <= MAX_DEAD_ZONE => Ok(Command::DeadZone(percent)),
+                _ => Err("NUMBER"),
+            }
+        }
+        b"BRIGHT" => {
+            if count != 2 {
+                return Err("ARGS");
+            }
+            match parse_u8(word(1)) {
+                Some(level) if (level as usize) < CONTRAST_LEVELS.len() => {
+                    Ok(Command::Brightness(level))
+                }
+                _ => Err("NUMBER"),
+            }
+        }
+        b"MOVE" => {
+            if count != 3 {
+                return Err("ARGS");
+            }
+            let x = parse_axis(word(1)).ok_or("NUMBER")?;
+            let y = parse_axis(word(2)).ok_or("NUMBER")?;
+            Ok(Command::Move(x, y))
+        }
+        b"DIFF" => {
+            if count != 2 {
+                return Err("ARGS");
+            }
+            match parse_u8(word(1)) {
+                Some(level) if level < DIFFICULTIES => Ok(Command::Difficulty(level)),
+                _ => Err("NUMBER"),
+            }
+        }
+        b"AXES" => {
+            if count != 4 {
+                return Err("ARGS");
+            }
+            let flag = |i: usize| match parse_u8(word(i)) {
+                Some(0) => Ok(false),
+                Some(1) => Ok(true),
+                _ => Err("NUMBER"),
+            };
+            Ok(Command::Axes(flag(1)?, flag(2)?, flag(3)?))
+        }
+        b"MAP" => {
+            if count != 1 + BUTTON_COUNT {
+                return Err("ARGS");
+            }
+            let mut roles = BUTTONS;
+            for (i, role) in roles.iter_mut().enumerate() {
+                let index = parse_u8(word(i + 1)).ok_or("NUMBER")?;
+                *role = *BUTTONS.get(index as usize).ok_or("NUMBER")?;
+            }
+            if !roles.contains(&Button::Stick) {
+                // no button could open the menu again
+                return Err("ARGS");
+            }
+            Ok(Command::Map(roles))
+        }
+        b"WEAPON" => {
+            if count != 2 {
+                return Err("ARGS");
+            }
+            match parse_u8(word(1)) {
+                Some(weapon) if weapon < WEAPON_COUNT => Ok(Command::Weapon(weapon)),
+                _ => Err("NUMBER"),
+            }
+        }
+        // the commands without arguments
+        name => {
+            let command = match name {
+                b"FIRE" => Command::Fire,
+                b"BOMB" => Command::Bomb,
+                b"PAUSE" => Command::Pause,
+                b"START" => Command::Start,
+                b"SCORE" => Command::Score,
+                _ => return Err("UNKNOWN"),
+            };
+            if count != 1 {
+                return Err("ARGS");
+            }
+            Ok(command)
+        }
+    }
+}
+
+fn send<W: Write<u8>>(tx: &mut W, bytes: &[u8]) {
+    for b in bytes {
+        block!(tx.write(*b)).ok();
+    }
+}
+
+pub fn reply<W: Write<u8>>(tx: &mut W, result: Result<(), &str>) {
+    match result {
+        Ok(()) => send(tx, b"OK\n"),
+        Err(reason) => {
+            send(tx, b"ERR ");
+            send(tx, reason.as_bytes());
+            send(tx, b"\n");
+        }
+    }
+}
+
+// "OK" and the numbers, for the queries
+pub fn reply_values<W: Write<u8>>(tx: &mut W, values: &[u32]) {
+    send(tx, b"OK");
+    for &value in values {
+        let mut buf = [0u8; 10];
+        send(tx, b" ");
+        send(tx, format_number(&mut buf, value, 1));
+    }
+    send(tx, b"\n");
+}
@@ -0,0 +1,299 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(target_os = "none")]
+use embedded_hal::blocking::spi::Write;
+#[cfg(target_os = "none")]
+use embedded_hal::digital::v2::OutputPin;
+#[cfg(target_os = "none")]
+use stm32f1xx_hal::dma::{dma1, Event};
+
+use super::*;
+
+// what the double buffer needs from the bus
+pub trait FlushLink {
+    // blocking, sent with dc low
+    fn command(&mut self, bytes: &[u8]);
+    // starts a background transfer with dc high. `transfer_done`
+    // must be called when it finishes
+    fn start_data(&mut self, data: &[u8]);
+    // true when `start_data` returns after the data is sent
+    fn blocking(&self) -> bool {
+        false
+    }
+}
+
+// two framebuffers: one is drawn while the other one goes to the
+// display, one page at a time
+pub struct DoubleBuffer {
+    frames: [FrameBuffer; 2],
+    // index of the frame being drawn
+    back: usize,
+    // columns of each page of the front frame still to be sent
+    spans: [Span; PAGES],
+    next_page: usize,
+    // true while the front frame is being sent in background
+    flushing: AtomicBool,
+}
+
+impl DoubleBuffer {
+    pub fn new() -> DoubleBuffer {
+        DoubleBuffer {
+            frames: [FrameBuffer::new(), FrameBuffer::new()],
+            back: 0,
+            spans: [CLEAN; PAGES],
+            next_page: PAGES,
+            flushing: AtomicBool::new(false),
+        }
+    }
+
+    pub fn back(&mut self) -> &mut FrameBuffer {
+        &mut self.frames[self.back]
+    }
+
+    pub fn busy(&self) -> bool {
+        self.flushing.load(Ordering::Acquire)
+    }
+
+    // swap the buffers and start sending the frame we just drew. false
+    // while the last one is still being sent, then nothing is swapped
+    pub fn present<L: FlushLink>(&mut self, link: &mut L) -> bool {
+        if self.busy() {
+            return false;
+        }
+        let front = 1 - self.back;
+
+        // the screen shows the front frame, whatever it painted
+        // must be erased or repainted too
+        for page in 0..PAGES {
+            let (first, last) = self.frames[self.back].dirty_span(page);
+            let (on_first, on_last) = self.frames[front].drawn_span(page);
+            self.spans[page] = (first.min(on_first), last.max(on_last));
+        }
+        self.frames[self.back].mark_flushed();
+
+        self.back = front;
+        self.next_page = 0;
+        self.flushing.store(true, Ordering::Release);
+        self.start_next(link);
+
+        // no interrupt chains the pages of a blocking link
+        while link.blocking() && self.busy() {
+            self.transfer_done(link);
+        }
+        true
+    }
+
+    // call from the transfer complete interrupt
+    pub fn transfer_done<L: FlushLink>(&mut self, link: &mut L) {
+        self.next_page += 1;
+        self.start_next(link);
+    }
+
+    fn start_next<L: FlushLink>(&mut self, link: &mut L) {
+        while self.next_page < PAGES {
+            let page = self.next_page;
+            let (first, last) = self.spans[page];
+            if first <= last {
+                let (window, len) = PANEL.window(page as u8, first, last);
+                link.command(&window[..len]);
+
+                let row = page * DISP_W as usize;
+                let front = &self.frames[1 - self.back];
+                link.start_data(&front.buf[row + first as usize..=row + last as usize]);
+                return;
+            }
+            self.next_page += 1;
+        }
+        self.flushing.store(false, Ordering::Release);
+    }
+}
+
+// display data over SPI1, sent by DMA1 channel 3
+#[cfg(target_os = "none")]
+pub struct SpiDmaLink<SPI, DC> {
+    spi: SPI,
+    dc: DC,
+    dma: dma1::C3,
+}
+
+#[cfg(target_os = "none")]
+impl<SPI, DC> SpiDmaLink<SPI, DC>
+where
+    SPI: Write<u8>,
+    DC: OutputPin,
+{
+    pub fn new(spi: SPI, dc: DC, mut dma: dma1::C3) -> SpiDmaLink<SPI, DC> {
+        let dr = unsafe { &(*pac::SPI1::ptr()).dr as *const _ as u32 };
+        dma.set_peripheral_address(dr, false);
+        dma.ch().cr.modify(|_, w| {
+            w.mem2mem()
+                .clear_bit()
+                .pl()
+                .medium()
+                .msize()
+                .bits8()
+                .psize()
+                .bits8()
+                .circ()
+                .clear_bit()
+                // read from memory
+                .dir()
+                .set_bit()
+        });
+        dma.listen(Event::TransferComplete);
+        SpiDmaLink { spi, dc, dma }
+    }
+
+    // call from the DMA1_CHANNEL3 interrupt, before `transfer_done`
+    pub fn finish(&mut self) {
+        self.dma.stop();
+
+        // the last byte is still being shifted out
+        let spi1 = unsafe { &*pac::SPI1::ptr() };
+        while spi1.sr.read().txe().bit_is_clear() {}
+        while spi1.sr.read().bsy().bit_is_set() {}
+        spi1.cr2.modify(|_, w| w.txdmaen().clear_bit());
+    }
+}
+
+#[cfg(target_os = "none")]
+impl<SPI, DC> FlushLink for SpiDmaLink<SPI, DC>
+where
+    SPI: Write<u8>,
+    DC: OutputPin,
+{
+    fn command(&mut self, bytes: &[u8]) {
+        self.dc.set_low().ok();
+        self.spi.write(bytes).ok();
+    }
+
+    fn start_data(&mut self, data: &[u8]) {
+        self.dc.set_high().ok();
+        self.dma.set_memory_address(data.as_ptr() as u32, true);
+        self.dma.set_transfer_length(data.len());
+
+        let spi1 = unsafe { &*pac::SPI1::ptr() };
+        spi1.cr2.modify(|_, w| w.txdmaen().set_bit());
+        self.dma.start();
+    }
+}
+
+// host side stand in for the DMA link, it only counts what would
+// be sent. call `transfer_done` by hand to play the interrupt
+#[cfg(not(target_os = "none"))]
+pub struct MockLink {
+    pub commands: usize,
+    pub data_bytes: usize,
+    pub transfers: usize,
+}
+
+#[cfg(not(target_os = "none"))]
+impl MockLink {
+    pub fn new() -> MockLink {
+        MockLink {
+            commands: 0,
+            data_bytes: 0,
+            transfers: 0,
+        }
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+impl FlushLink for MockLink {
+    fn command(&mut self, bytes: &[u8]) {
+        self.commands += bytes.len();
+    }
+
+    fn start_data(&mut self, data: &[u8]) {
+        self.data_bytes += data.len();
+        self.transfers += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // plays the transfer complete interrupt until the frame is out
+    fn finish(frames: &mut DoubleBuffer, link: &mut MockLink) {
+        while frames.busy() {
+            frames.transfer_done(link);
+        }
+    }
+
+    fn back(frames: &mut DoubleBuffer) -> *const FrameBuffer {
+        frames.back()
+    }
+
+    #[test]
+    fn present_sends_a_page_at_a_time() {
+        let mut frames = DoubleBuffer::new();
+        let mut link = MockLink::new();
+        assert!(!frames.busy());
+
+        assert!(frames.present(&mut link));
+        assert!(frames.busy());
+        assert_eq!(link.transfers, 1);
+
+        finish(&mut frames, &mut link);
+        assert_eq!(link.transfers, PAGES);
+        // display ram starts with garbage, the first frame goes out whole
+        assert_eq!(link.data_bytes, BUF_SIZE);
+    }
+
+    #[test]
+    fn back_buffer_flips() {
+        let mut frames = DoubleBuffer::new();
+        let mut link = MockLink::new();
+        let first = back(&mut frames);
+
+        frames.present(&mut link);
+        finish(&mut frames, &mut link);
+        let second = back(&mut frames);
+        assert!(second != first);
+
+        frames.present(&mut link);
+        finish(&mut frames, &mut link);
+        assert!(back(&mut frames) == first);
+    }
+
+    #[test]
+    fn no_swap_while_flushing() {
+        let mut frames = DoubleBuffer::new();
+        let mut link = MockLink::new();
+        frames.present(&mut link);
+        let drawing = back(&mut frames);
+
+        assert!(!frames.present(&mut link));
+        assert!(back(&mut frames) == drawing);
+        assert_eq!(link.transfers, 1);
+
+        finish(&mut frames, &mut link);
+        assert!(frames.present(&mut link));
+        assert!(back(&mut frames) != drawing);
+    }
+
+    #[test]
+    fn only_changed_columns_are_sent() {
+        let mut frames = DoubleBuffer::new();
+        let mut link = MockLink::new();
+        // both frames start dirty
+        for _ in 0..2 {
+            frames.present(&mut link);
+            finish(&mut frames, &mut link);
+        }
+
+        link = MockLink::new();
+        frames.back().set_pixel(5, 9, true);
+        frames.present(&mut link);
+        finish(&mut frames, &mut link);
+        assert_eq!((link.transfers, link.data_bytes), (1, 1));
+
+        // the pixel is on screen, the next frame must erase it
+        link = MockLink::new();
+        frames.back().clear();
+        frames.present(&mut link);
+        finish(&mut frames, &mut link);
+        assert_eq!((link.transfers, link.data_bytes), (1, 1));
+    }
+}
@@ -0,0 +1,153 @@
+use super::*;
+
+// ssd1306 default contrast, the one `Panel::init` sets
+pub const CONTRAST: u8 = 0x5F;
+
+// invert flashes and fades use the display commands, so they cost
+// nothing to draw. shake moves the world under the hud
+pub struct Effects {
+    shake: u8,
+    shake_amp: i16,
+    offset: (i16, i16),
+    flash: u8,
+    // fade from 0 to `brightness` (in) or the opposite (out)
+    fade: u8,
+    fade_len: u8,
+    fade_in: bool,
+    // contrast when not fading
+    pub brightness: u8,
+    // screen saver, see BurnIn
+    pub dim: bool,
+    pub blank: bool,
+    // last values sent to the display, if any
+    synced: bool,
+    sent_invert: bool,
+    sent_contrast: u8,
+    sent_blank: bool,
+}
+
+impl Effects {
+    pub fn new() -> Effects {
+        Effects {
+            shake: 0,
+            shake_amp: 0,
+            offset: (0, 0),
+            flash: 0,
+            fade: 0,
+            fade_len: 0,
+            fade_in: true,
+            brightness: CONTRAST,
+            dim: false,
+            blank: false,
+            synced: false,
+            sent_invert: false,
+            sent_contrast: CONTRAST,
+            sent_blank: false,
+        }
+    }
+
+    pub fn shake(&mut self, ticks: u8, amp: i16) {
+        self.shake = self.shake.max(ticks);
+        self.shake_amp = self.shake_amp.max(amp);
+    }
+
+    pub fn flash(&mut self, ticks: u8) {
+        self.flash = self.flash.max(ticks);
+    }
+
+    pub fn fade_in(&mut self, ticks: u8) {
+        self.fade = ticks;
+        self.fade_len = ticks;
+        self.fade_in = true;
+    }
+
+    pub fn fade_out(&mut self, ticks: u8) {
+        self.fade = ticks;
+        self.fade_len = ticks;
+        self.fade_in = false;
+    }
+
+    pub fn tick(&mut self, random: &mut Xorshift128pState) {
+        if self.shake > 0 {
+            self.shake -= 1;
+            let amp = self.shake_amp as u64;
+            self.offset = (
+                random.gen_min_max(0, amp * 2 + 1) as i16 - self.shake_amp,
+                random.gen_min_max(0, amp * 2 + 1) as i16 - self.shake_amp,
+            );
+        } else {
+            self.shake_amp = 0;
+            self.offset = (0, 0);
+        }
+
+        if self.flash > 0 {
+            self.flash -= 1;
+        }
+
+        // a finished fade out stays dark
+        if self.fade > 1 || (self.fade == 1 && self.fade_in) {
+            self.fade -= 1;
+        }
+    }
+
+    // camera offset for this frame
+    pub fn offset(&self) -> (i16, i16) {
+        self.offset
+    }
+
+    pub fn contrast(&self) -> u8 {
+        let brightness = if self.dim {
+            self.brightness / 4
+        } else {
+            self.brightness
+        };
+        if self.fade == 0 {
+            return brightness;
+        }
+        let done = (self.fade_len - self.fade) as u16;
+        let level = if self.fade_in {
+            done
+        } else {
+            self.fade_len as u16 - done - 1
+        };
+        (brightness as u16 * level / self.fade_len as u16) as u8
+    }
+
+    // send the display commands that changed. the link must be idle
+    pub fn apply<L: FlushLink>(&mut self, link: &mut L) {
+        let invert = self.flash > 0;
+        if invert != self.sent_invert || !self.synced {
+            link.command(&[0xA6 | invert as u8]);
+            self.sent_invert = invert;
+        }
+
+        let contrast = self.contrast();
+        if contrast != self.sent_contrast || !self.synced {
+            link.command(&[0x81, contrast]);
+            self.sent_contrast = contrast;
+        }
+
+        // display off keeps the ram, waking up shows the last frame
+        if self.blank != self.sent_blank || !self.synced {
+            link.command(&[0xAE | !self.blank as u8]);
+            self.sent_blank = self.blank;
+        }
+        self.synced = true;
+    }
+}
+
+// moves everything drawn through it, keeping it inside the clip rows
+pub struct Shifted<'a, C> {
+    pub canvas: &'a mut C,
+    pub offset: (i16, i16),
+    pub clip: (i16, i16),
+}
+
+impl<'a, C: Canvas> Canvas for Shifted<'a, C> {
+    fn set_pixel(&mut self, x: i16, y: i16, on: bool) {
+        let (x, y) = (x + self.offset.0, y + self.offset.1);
+        if y >= self.clip.0 && y < self.clip.1 {
+            self.canvas.set_pixel(x, y, on);
+        }
+    }
+}
@@ -7,9 +7,12 @@ pub struct Entity {
     pub typ: i16,
     pub del: bool,
     pub state: u8,
-    pub sprite_x: u8,
-    pub sprite_y: u8,
+    // index in SPRITE_ASSETS and animation frame of the asset
+    pub sprite: u8,
+    pub frame: u8,
     pub speed: u16,
+    // gray level 0..=3 of a 1 plane sprite
+    pub shade: u8,
 }
 
 impl Entity {
@@ -22,9 +25,10 @@ impl Entity {
             typ: 0i16,
             del: true,
             state: 0u8,
-            sprite_x: 0u8,
-            sprite_y: 0u8,
+            sprite: 0u8,
+            frame: 0u8,
             speed: 0u16,
+            shade: 3u8,
         }
     }
-}
\ No newline at end of file
+}
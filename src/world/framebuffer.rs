@@ -0,0 +1,104 @@
+use super::{Canvas, DISP_H, DISP_W};
+
+// the ssd1306 ram is split in pages of 8 rows, each byte is one
+// column of a page with bit 0 on top
+pub const PAGES: usize = (DISP_H / 8) as usize;
+pub const BUF_SIZE: usize = DISP_W as usize * PAGES;
+
+// (first, last) column of a page, first > last means clean
+pub type Span = (u8, u8);
+pub const CLEAN: Span = (u8::MAX, 0);
+
+fn join(a: Span, b: Span) -> Span {
+    (a.0.min(b.0), a.1.max(b.1))
+}
+
+pub struct FrameBuffer {
+    pub buf: [u8; BUF_SIZE],
+    // columns painted since the last clear
+    drawn: [Span; PAGES],
+    // columns that changed on screen since the last flush
+    dirty: [Span; PAGES],
+}
+
+impl FrameBuffer {
+    pub fn new() -> FrameBuffer {
+        FrameBuffer {
+            buf: [0u8; BUF_SIZE],
+            drawn: [CLEAN; PAGES],
+            // display ram starts with garbage, send everything once
+            dirty: [(0, DISP_W as u8 - 1); PAGES],
+        }
+    }
+
+    // only the columns painted in the last frame need to be erased
+    pub fn clear(&mut self) {
+        for page in 0..PAGES {
+            let (first, last) = self.drawn[page];
+            if first <= last {
+                let row = page * DISP_W as usize;
+                for b in &mut self.buf[row + first as usize..=row + last as usize] {
+                    *b = 0;
+                }
+                self.dirty[page] = join(self.dirty[page], self.drawn[page]);
+            }
+            self.drawn[page] = CLEAN;
+        }
+    }
+
+    // changed columns of a page, to be sent on the next flush
+    pub fn dirty_span(&self, page: usize) -> Span {
+        join(self.dirty[page], self.drawn[page])
+    }
+
+    // columns painted in this frame
+    pub fn drawn_span(&self, page: usize) -> Span {
+        self.drawn[page]
+    }
+
+    pub fn mark_flushed(&mut self) {
+        self.dirty = [CLEAN; PAGES];
+    }
+}
+
+impl Canvas for FrameBuffer {
+    fn set_pixel(&mut self, x: i16, y: i16, on: bool) {
+        // avoid print to non existing display coord
+        if x < 0 || x >= DISP_W || y < 0 || y >= DISP_H {
+            return;
+        }
+        let page = (y / 8) as usize;
+        let byte = &mut self.buf[page * DISP_W as usize + x as usize];
+        if on {
+            *byte |= 1 << (y % 8);
+        } else {
+            *byte &= !(1 << (y % 8));
+        }
+        self.drawn[page] = join(self.drawn[page], (x as u8, x as u8));
+    }
+
+    // a byte mask per page instead of a pixel at a time
+    fn fill_rect(&mut self, x: i16, y: i16, w: i16, h: i16, on: bool) {
+        let (x0, x1) = (x.max(0), (x + w).min(DISP_W));
+        let (y0, y1) = (y.max(0), (y + h).min(DISP_H));
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+
+        for page in (y0 / 8) as usize..=((y1 - 1) / 8) as usize {
+            let top = (y0 - page as i16 * 8).max(0);
+            let bottom = (y1 - page as i16 * 8).min(8);
+            let mask = (((1u16 << bottom) - 1) & !((1u16 << top) - 1)) as u8;
+
+            let row = page * DISP_W as usize;
+            for byte in &mut self.buf[row + x0 as usize..row + x1 as usize] {
+                if on {
+                    *byte |= mask;
+                } else {
+                    *byte &= !mask;
+                }
+            }
+            self.drawn[page] = join(self.drawn[page], (x0 as u8, x1 as u8 - 1));
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use super::*;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum HudBand {
+    Top,
+    Bottom,
+}
+
+// 5x7 icons, same column format as the font
+const ICON_LIFE: [u8; 5] = [0x0C, 0x1E, 0x3C, 0x1E, 0x0C];
+const ICON_BOMB: [u8; 5] = [0x38, 0x7C, 0x7E, 0x7D, 0x38];
+
+// screen band reserved for the hud. elements are placed by their x
+// inside the band, None hides the element
+#[derive(Copy, Clone)]
+pub struct Hud {
+    pub band: HudBand,
+    pub height: i16,
+    // hidden by default, the game has one life and the bomb is always
+    // ready
+    pub lives_x: Option<i16>,
+    pub bombs_x: Option<i16>,
+    pub level_x: Option<i16>,
+    // x and width of the boss health bar
+    pub boss_bar: Option<(i16, i16)>,
+    // score is right aligned at this x
+    pub score_x: Option<i16>,
+    // moves the whole hud a pixel or so against burn-in
+    pub shift: (i16, i16),
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud {
+            band: HudBand::Top,
+            height: LINE_H,
+            lives_x: None,
+            bombs_x: None,
+            level_x: Some(0),
+            boss_bar: Some((58, 36)),
+            score_x: Some(DISP_W - 1),
+            shift: (0, 0),
+        }
+    }
+
+    // first row of the band
+    pub fn top(&self) -> i16 {
+        match self.band {
+            HudBand::Top => 0,
+            HudBand::Bottom => DISP_H - self.height,
+        }
+    }
+
+    // rows left for the game, as (first row, one past the last row)
+    pub fn play_area(&self) -> (i16, i16) {
+        match self.band {
+            HudBand::Top => (self.height, DISP_H),
+            HudBand::Bottom => (0, DISP_H - self.height),
+        }
+    }
+
+    pub fn draw<C: Canvas>(&self, canvas: &mut C, world: &World) {
+        let (dx, dy) = self.shift;
+        let y = self.top() + dy;
+
+        if let Some(x) = self.lives_x.map(|x| x + dx) {
+            draw_glyph(canvas, x, y, &ICON_LIFE);
+            draw_number(canvas, x + ADVANCE, y, world.lives as u32, 1, Align::Left);
+        }
+
+        if let Some(x) = self.bombs_x.map(|x| x + dx) {
+            draw_glyph(canvas, x, y, &ICON_BOMB);
+            draw_number(canvas, x + ADVANCE, y, world.bombs as u32, 1, Align::Left);
+        }
+
+        if let Some(x) = self.level_x.map(|x| x + dx) {
+            draw_text(canvas, x, y, b"L", Align::Left);
+            draw_number(canvas, x + ADVANCE, y, world.level as u32, 1, Align::Left);
+        }
+
+        // only shown while a boss is alive
+        if let Some((x, w)) = self.boss_bar {
+            if world.boss_hp_max > 0 {
+                let filled = (w - 2) as u32 * world.boss_hp as u32 / world.boss_hp_max as u32;
+                canvas.rect(x + dx, y + 1, w, 5, true);
+                canvas.fill_rect(x + dx + 1, y + 2, filled as i16, 3, true);
+            }
+        }
+
+        if let Some(x) = self.score_x.map(|x| x + dx) {
+            draw_number(canvas, x, y, world.score, 5, Align::Right);
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use super::*;
+
+// one frame of controls from one backend
+#[derive(Copy, Clone, PartialEq)]
+pub struct InputFrame {
+    // -STICK_MAX..=STICK_MAX, dead zone already applied
+    pub x_axis: i16,
+    pub y_axis: i16,
+    // raw levels in the order of Button, debounced after the merge
+    pub buttons: [bool; BUTTON_COUNT],
+}
+
+impl InputFrame {
+    pub const fn new() -> InputFrame {
+        InputFrame {
+            x_axis: 0,
+            y_axis: 0,
+            buttons: [false; BUTTON_COUNT],
+        }
+    }
+
+    fn moved(&self) -> bool {
+        self.x_axis != 0 || self.y_axis != 0
+    }
+}
+
+// how the board controls are mounted. sticks come rotated or flipped,
+// and any button can do the job of any other
+#[derive(Copy, Clone, PartialEq)]
+pub struct InputMap {
+    // adc1 steers up and down instead of left and right
+    pub swap_axes: bool,
+    // after the swap
+    pub invert_x: bool,
+    pub invert_y: bool,
+    // the Button each board button acts as, in the order of Button
+    pub buttons: [Button; BUTTON_COUNT],
+}
+
+pub const INPUT_MAP_BYTES: usize = 1 + BUTTON_COUNT;
+
+impl InputMap {
+    pub const fn new() -> InputMap {
+        InputMap {
+            swap_axes: false,
+            invert_x: false,
+            invert_y: false,
+            buttons: BUTTONS,
+        }
+    }
+
+    pub fn apply(&self, frame: InputFrame) -> InputFrame {
+        let (mut x, mut y) = (frame.x_axis, frame.y_axis);
+        if self.swap_axes {
+            core::mem::swap(&mut x, &mut y);
+        }
+        if self.invert_x {
+            x = -x;
+        }
+        if self.invert_y {
+            y = -y;
+        }
+        let mut buttons = [false; BUTTON_COUNT];
+        for (&button, &down) in self.buttons.iter().zip(frame.buttons.iter()) {
+            buttons[button as usize] |= down;
+        }
+        InputFrame {
+            x_axis: x,
+            y_axis: y,
+            buttons,
+        }
+    }
+
+    // some board button must open the menu, or the map can't be undone
+    pub fn has_menu(&self) -> bool {
+        self.buttons.contains(&Button::Stick)
+    }
+
+    pub fn bytes(&self) -> [u8; INPUT_MAP_BYTES] {
+        let mut bytes = [0u8; INPUT_MAP_BYTES];
+        bytes[0] = self.swap_axes as u8 | (self.invert_x as u8) << 1 | (self.invert_y as u8) << 2;
+        for (b, &button) in bytes[1..].iter_mut().zip(self.buttons.iter()) {
+            *b = button as u8;
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<InputMap> {
+        if bytes.len() < INPUT_MAP_BYTES {
+            return None;
+        }
+        let mut map = InputMap::new();
+        map.swap_axes = bytes[0] & 1 > 0;
+        map.invert_x = bytes[0] & 2 > 0;
+        map.invert_y = bytes[0] & 4 > 0;
+        for (button, &b) in map.buttons.iter_mut().zip(bytes[1..].iter()) {
+            *button = *BUTTONS.get(b as usize)?;
+        }
+        if !map.has_menu() {
+            return None;
+        }
+        Some(map)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Source {
+    // adc joystick and the buttons on the board
+    Gpio,
+    // serial gamepad on USART1, see PadReader
+    Pad,
+    // host only
+    Keyboard,
+}
+
+pub const SOURCE_COUNT: usize = 3;
+
+// merges the backends into one frame. the axes come from the source
+// moved last, so an idle stick doesn't fight the phone. buttons of
+// all plugged sources are or-ed
+pub struct InputManager {
+    // None when unplugged
+    frames: [Option<InputFrame>; SOURCE_COUNT],
+    steering: Source,
+}
+
+impl InputManager {
+    pub fn new() -> InputManager {
+        InputManager {
+            frames: [None; SOURCE_COUNT],
+            steering: Source::Gpio,
+        }
+    }
+
+    // once per frame for every source, None when it is unplugged
+    pub fn set(&mut self, source: Source, frame: Option<InputFrame>) {
+        if let Some(frame) = frame {
+            let steering = self.frames[self.steering as usize];
+            if frame.moved() && !steering.map_or(false, |f| f.moved()) {
+                self.steering = source;
+            }
+        }
+        self.frames[source as usize] = frame;
+    }
+
+    pub fn plugged(&self, source: Source) -> bool {
+        self.frames[source as usize].is_some()
+    }
+
+    pub fn merge(&self) -> InputFrame {
+        let mut merged = self.frames[self.steering as usize].unwrap_or(InputFrame::new());
+        for frame in self.frames.iter().flatten() {
+            for (out, &down) in merged.buttons.iter_mut().zip(frame.buttons.iter()) {
+                *out |= down;
+            }
+        }
+        merged
+    }
+}
+
+// binary gamepad frames on the serial port, e.g. from a phone app over
+// the hc-05. they share the line with the text console, the start byte
+// can't appear in a text command:
+//
+//   PAD_START x y buttons sum
+//
+// x and y are i8 in -STICK_MAX..=STICK_MAX, positive is right and
+// down. bit i of buttons is Button i. sum is the wrapping sum of the
+// three bytes before it. send a frame on every change and at least
+// once per PAD_TIMEOUT
+pub const PAD_START: u8 = 0xA5;
+pub const PAD_TIMEOUT: u16 = FRAME_HZ as u16;
+
+pub struct PadReader {
+    buf: [u8; 4],
+    // bytes of `buf` received, None between frames
+    len: Option<usize>,
+    frame: InputFrame,
+    // frames since the last good pad frame
+    silent: u16,
+}
+
+impl PadReader {
+    pub fn new() -> PadReader {
+        PadReader {
+            buf: [0; 4],
+            len: None,
+            frame: InputFrame::new(),
+            silent: PAD_TIMEOUT,
+        }
+    }
+
+    // false when the byte is not part of a pad frame, it goes to the
+    // console then
+    pub fn push(&mut self, byte: u8) -> bool {
+        let len = match self.len {
+            None if byte == PAD_START => {
+                self.len = Some(0);
+                return true;
+            }
+            None => return false,
+            Some(len) => len,
+        };
+        self.buf[len] = byte;
+        if len + 1 < self.buf.len() {
+            self.len = Some(len + 1);
+            return true;
+        }
+        self.len = None;
+
+        let [x, y, buttons, sum] = self.buf;
+        if x.wrapping_add(y).wrapping_add(buttons) != sum {
+            return true;
+        }
+        let axis = |b: u8| (b as i8 as i16).max(-STICK_MAX).min(STICK_MAX);
+        self.frame.x_axis = axis(x);
+        self.frame.y_axis = axis(y);
+        for (i, down) in self.frame.buttons.iter_mut().enumerate() {
+            *down = buttons >> i & 1 > 0;
+        }
+        self.silent = 0;
+        true
+    }
+
+    // steering from a text command, it times out like a frame
+    pub fn steer(&mut self, x: i16, y: i16) {
+        if self.silent >= PAD_TIMEOUT {
+            self.frame = InputFrame::new();
+        }
+        self.frame.x_axis = x;
+        self.frame.y_axis = y;
+        self.silent = 0;
+    }
+
+    // once per frame. a pad that went quiet is unplugged, its last
+    // frame must not keep the ship moving
+    pub fn tick(&mut self) -> Option<InputFrame> {
+        if self.silent >= PAD_TIMEOUT {
+            return None;
+        }
+        self.silent += 1;
+        Some(self.frame)
+    }
+}
+
+// host keyboard: wasd steers at full tilt, space is A, b is B and c
+// the stick switch
+#[cfg(not(target_os = "none"))]
+pub struct Keyboard {
+    // up, left, down, right, then the buttons
+    keys: [bool; 4 + BUTTON_COUNT],
+}
+
+#[cfg(not(target_os = "none"))]
+impl Keyboard {
+    pub fn new() -> Keyboard {
+        Keyboard {
+            keys: [false; 4 + BUTTON_COUNT],
+        }
+    }
+
+    pub fn key(&mut self, key: char, down: bool) {
+        let index = match key {
+            'w' => 0,
+            'a' => 1,
+            's' => 2,
+            'd' => 3,
+            ' ' => 4 + Button::A as usize,
+            'b' => 4 + Button::B as usize,
+            'c' => 4 + Button::Stick as usize,
+            _ => return,
+        };
+        self.keys[index] = down;
+    }
+
+    pub fn frame(&self) -> InputFrame {
+        let axis = |minus: bool, plus: bool| (plus as i16 - minus as i16) * STICK_MAX;
+        let mut frame = InputFrame::new();
+        frame.x_axis = axis(self.keys[1], self.keys[3]);
+        frame.y_axis = axis(self.keys[0], self.keys[2]);
+        frame.buttons.copy_from_slice(&self.keys[4..]);
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(x: i16, y: i16, buttons: [bool; BUTTON_COUNT]) -> InputFrame {
+        InputFrame {
+            x_axis: x,
+            y_axis: y,
+            buttons,
+        }
+    }
+
+    const NONE: [bool; BUTTON_COUNT] = [false; BUTTON_COUNT];
+
+    #[test]
+    fn keyboard_frame() {
+        let mut keyboard = Keyboard::new();
+        keyboard.key('d', true);
+        keyboard.key('w', true);
+        keyboard.key(' ', true);
+        keyboard.key('x', true);
+        assert!(keyboard.frame() == frame(STICK_MAX, -STICK_MAX, [true, false, false]));
+
+        // opposite keys cancel
+        keyboard.key('a', true);
+        keyboard.key('w', false);
+        keyboard.key(' ', false);
+        keyboard.key('c', true);
+        assert!(keyboard.frame() == frame(0, 0, [false, false, true]));
+    }
+
+    #[test]
+    fn pad_steers_past_an_idle_stick() {
+        let mut inputs = InputManager::new();
+        inputs.set(Source::Gpio, Some(frame(0, 0, NONE)));
+        inputs.set(Source::Pad, Some(frame(-40, 20, NONE)));
+        let merged = inputs.merge();
+        assert_eq!((merged.x_axis, merged.y_axis), (-40, 20));
+
+        // the stick takes over once the pad lets go
+        inputs.set(Source::Gpio, Some(frame(0, 0, NONE)));
+        inputs.set(Source::Pad, Some(frame(0, 0, NONE)));
+        inputs.set(Source::Gpio, Some(frame(60, 0, NONE)));
+        inputs.set(Source::Pad, Some(frame(0, 0, NONE)));
+        assert_eq!(inputs.merge().x_axis, 60);
+    }
+
+    #[test]
+    fn moving_stick_keeps_steering() {
+        let mut inputs = InputManager::new();
+        inputs.set(Source::Gpio, Some(frame(50, 0, NONE)));
+        inputs.set(Source::Pad, Some(frame(-40, 0, NONE)));
+        assert_eq!(inputs.merge().x_axis, 50);
+
+        inputs.set(Source::Keyboard, Some(frame(0, STICK_MAX, NONE)));
+        assert_eq!(inputs.merge().y_axis, 0);
+    }
+
+    #[test]
+    fn unplugged_source_stops_steering() {
+        let mut inputs = InputManager::new();
+        inputs.set(Source::Pad, Some(frame(-40, 0, [true, false, false])));
+        inputs.set(Source::Pad, None);
+        assert!(!inputs.plugged(Source::Pad));
+        assert!(inputs.merge() == InputFrame::new());
+    }
+
+    #[test]
+    fn buttons_are_or_ed() {
+        let mut keyboard = Keyboard::new();
+        keyboard.key('b', true);
+        let mut inputs = InputManager::new();
+        inputs.set(Source::Gpio, Some(frame(0, 0, [true, false, false])));
+        inputs.set(Source::Pad, Some(frame(30, 0, NONE)));
+        inputs.set(Source::Keyboard, Some(keyboard.frame()));
+        let merged = inputs.merge();
+        assert!(merged == frame(30, 0, [true, true, false]));
+    }
+}
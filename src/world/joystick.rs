@@ -0,0 +1,295 @@
+// analog stick on PA1/PA2. raw adc readings go through a per axis
+// calibration and a radial dead zone, and come out in
+// -STICK_MAX..=STICK_MAX. both axes read lower when pushed right/down
+use super::*;
+
+pub const STICK_MAX: i16 = 100;
+// resting readings averaged for the centre
+pub const CENTER_SAMPLES: usize = 8;
+// frames of stick waggling in a full calibration
+pub const CALIBRATE_FRAMES: u16 = 5 * FRAME_HZ as u16;
+// a calibrated axis must move at least this far each way
+const MIN_TRAVEL: u16 = 300;
+// percent, a wider dead zone leaves too little travel to steer with
+pub const MAX_DEAD_ZONE: u8 = 90;
+// resting readings of a wired stick stay this close together, an
+// unwired adc pin floats further or sits on a rail
+const REST_NOISE: u16 = 100;
+const RAIL: u16 = 100;
+
+// probes in a row that must fail before a wired stick counts as
+// pulled out, one probe a second
+const LOST_PROBES: u8 = 3;
+
+// from readings of one axis taken at rest
+pub fn stick_present(samples: &[u16]) -> bool {
+    let min = samples.iter().copied().min().unwrap_or(0);
+    let max = samples.iter().copied().max().unwrap_or(0);
+    max - min <= REST_NOISE && min >= RAIL && max <= 4095 - RAIL
+}
+
+// mean of a burst of readings
+pub fn average(samples: &[u16]) -> u16 {
+    let sum: u32 = samples.iter().map(|&v| v as u32).sum();
+    (sum / samples.len().max(1) as u32) as u16
+}
+
+// readings of one axis taken back to back, too quick for a wired stick
+// to move between them
+fn noisy(samples: &[u16]) -> bool {
+    let min = samples.iter().copied().min().unwrap_or(0);
+    let max = samples.iter().copied().max().unwrap_or(0);
+    max - min > REST_NOISE
+}
+
+fn railed(samples: &[u16]) -> bool {
+    samples.iter().all(|&v| v < RAIL || v > 4095 - RAIL)
+}
+
+// hot plug of the stick. a burst of readings once a second: while
+// unplugged a stick at rest shows up, while wired floating pins show
+// up as noise, or as both axes on a rail. a stick pushed all the way
+// rails one axis at most, and only for a while
+pub struct StickDetect {
+    pub wired: bool,
+    // frames to the next probe
+    probe: u16,
+    // failed probes in a row while wired
+    misses: u8,
+}
+
+impl StickDetect {
+    pub fn new() -> StickDetect {
+        StickDetect {
+            wired: false,
+            probe: 0,
+            misses: 0,
+        }
+    }
+
+    // once per frame, true when a burst should be read for `update`
+    pub fn due(&mut self) -> bool {
+        if self.probe > 0 {
+            self.probe -= 1;
+            return false;
+        }
+        self.probe = FRAME_HZ as u16;
+        true
+    }
+
+    // true when the stick was just plugged in, the burst is at rest
+    // then and makes a centre
+    pub fn update(&mut self, xs: &[u16], ys: &[u16]) -> bool {
+        if !self.wired {
+            self.wired = stick_present(xs) && stick_present(ys);
+            self.misses = 0;
+            return self.wired;
+        }
+        if noisy(xs) || noisy(ys) || (railed(xs) && railed(ys)) {
+            self.misses += 1;
+            if self.misses >= LOST_PROBES {
+                self.wired = false;
+                self.misses = 0;
+            }
+        } else {
+            self.misses = 0;
+        }
+        false
+    }
+}
+
+// raw readings of one axis
+#[derive(Copy, Clone, PartialEq)]
+pub struct Axis {
+    pub min: u16,
+    pub center: u16,
+    pub max: u16,
+}
+
+impl Axis {
+    // fits the old fixed thresholds: centre 2000 on a 12 bit adc
+    pub const fn new() -> Axis {
+        Axis {
+            min: 0,
+            center: 2000,
+            max: 4095,
+        }
+    }
+
+    // -STICK_MAX..=STICK_MAX, before the dead zone
+    pub fn value(&self, raw: u16) -> i16 {
+        let raw = raw.max(self.min).min(self.max) as i32;
+        let center = self.center as i32;
+        let span = if raw < center {
+            center - self.min as i32
+        } else {
+            self.max as i32 - center
+        };
+        if span == 0 {
+            return 0;
+        }
+        ((center - raw) * STICK_MAX as i32 / span) as i16
+    }
+
+    fn widen(&mut self, raw: u16) {
+        self.min = self.min.min(raw);
+        self.max = self.max.max(raw);
+    }
+
+    fn usable(&self) -> bool {
+        self.center.saturating_sub(self.min) >= MIN_TRAVEL
+            && self.max.saturating_sub(self.center) >= MIN_TRAVEL
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct Calibration {
+    pub x: Axis,
+    pub y: Axis,
+    // percent of STICK_MAX around the centre that reads as no input
+    pub dead_zone: u8,
+}
+
+fn isqrt(n: u32) -> u32 {
+    let mut root = 0;
+    let mut bit = 1 << 30;
+    let mut n = n;
+    while bit > n {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if n >= root + bit {
+            n -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+impl Calibration {
+    pub const fn new() -> Calibration {
+        Calibration {
+            x: Axis::new(),
+            y: Axis::new(),
+            dead_zone: 20,
+        }
+    }
+
+    pub fn set_dead_zone(&mut self, percent: u8) {
+        self.dead_zone = percent.min(MAX_DEAD_ZONE);
+    }
+
+    // boot centre. a stick held away from its stored centre would make
+    // the ship drift, so then the stored one is kept
+    pub fn set_center(&mut self, x: u16, y: u16) {
+        let mut moved = *self;
+        moved.x.center = x;
+        moved.y.center = y;
+        if self.read(x, y) == (0, 0) && moved.x.usable() && moved.y.usable() {
+            *self = moved;
+        }
+    }
+
+    // stick position with the dead zone applied. the direction is kept
+    // and the magnitude is stretched so the zone edge reads as zero
+    pub fn read(&self, raw_x: u16, raw_y: u16) -> (i16, i16) {
+        let (x, y) = (self.x.value(raw_x) as i32, self.y.value(raw_y) as i32);
+        let len = isqrt((x * x + y * y) as u32) as i32;
+        let dead = self.dead_zone as i32 * STICK_MAX as i32 / 100;
+        if len <= dead {
+            return (0, 0);
+        }
+        let max = STICK_MAX as i32;
+        let scaled = ((len - dead) * max / (max - dead)).min(max);
+        ((x * scaled / len) as i16, (y * scaled / len) as i16)
+    }
+}
+
+// full calibration: the stick rests at the start for the centre, then
+// gets moved around to all its edges
+pub struct Calibrator {
+    cal: Calibration,
+    frames_left: u16,
+}
+
+impl Calibrator {
+    pub fn new(current: Calibration, raw_x: u16, raw_y: u16) -> Calibrator {
+        let at = |raw| Axis {
+            min: raw,
+            center: raw,
+            max: raw,
+        };
+        Calibrator {
+            cal: Calibration {
+                x: at(raw_x),
+                y: at(raw_y),
+                dead_zone: current.dead_zone,
+            },
+            frames_left: CALIBRATE_FRAMES,
+        }
+    }
+
+    // one frame of readings, the new calibration once done. Err when
+    // the stick did not travel far enough, then keep the old one
+    pub fn sample(&mut self, raw_x: u16, raw_y: u16) -> Option<Result<Calibration, &'static str>> {
+        self.cal.x.widen(raw_x);
+        self.cal.y.widen(raw_y);
+        self.frames_left -= 1;
+        if self.frames_left > 0 {
+            return None;
+        }
+        if self.cal.x.usable() && self.cal.y.usable() {
+            Some(Ok(self.cal))
+        } else {
+            Some(Err("TRAVEL"))
+        }
+    }
+}
+
+pub const CALIBRATION_BYTES: usize = 13;
+
+impl Calibration {
+    pub fn bytes(&self) -> [u8; CALIBRATION_BYTES] {
+        let mut bytes = [0u8; CALIBRATION_BYTES];
+        let values = [
+            self.x.min,
+            self.x.center,
+            self.x.max,
+            self.y.min,
+            self.y.center,
+            self.y.max,
+        ];
+        for (i, value) in values.iter().enumerate() {
+            bytes[i * 2..i * 2 + 2].copy_from_slice(&value.to_le_bytes());
+        }
+        bytes[12] = self.dead_zone;
+        bytes
+    }
+
+    // None when the bytes don't make a usable calibration
+    pub fn from_bytes(bytes: &[u8]) -> Option<Calibration> {
+        if bytes.len() < CALIBRATION_BYTES {
+            return None;
+        }
+        let value = |i: usize| u16::from_le_bytes([bytes[i * 2], bytes[i * 2 + 1]]);
+        let axis = |i: usize| Axis {
+            min: value(i),
+            center: value(i + 1),
+            max: value(i + 2),
+        };
+        let mut cal = Calibration {
+            x: axis(0),
+            y: axis(3),
+            dead_zone: 0,
+        };
+        cal.set_dead_zone(bytes[12]);
+        if cal.x.usable() && cal.y.usable() {
+            Some(cal)
+        } else {
+            None
+        }
+    }
+}
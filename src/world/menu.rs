@@ -0,0 +1,135 @@
+use super::*;
+
+// the stick steps through the menu on a push, then this often while
+// held, in frames
+pub const MENU_DELAY: u16 = FRAME_HZ as u16 / 2;
+pub const MENU_REPEAT: u16 = FRAME_HZ as u16 / 8;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum MenuItem {
+    SwapAxes,
+    InvertX,
+    InvertY,
+    // what each board button does
+    Map(Button),
+    Calibrate,
+}
+
+const MENU_ITEMS: [MenuItem; 7] = [
+    MenuItem::SwapAxes,
+    MenuItem::InvertX,
+    MenuItem::InvertY,
+    MenuItem::Map(Button::A),
+    MenuItem::Map(Button::B),
+    MenuItem::Map(Button::Stick),
+    MenuItem::Calibrate,
+];
+
+// in the order of Button: the board buttons and what they can do
+const BUTTON_NAMES: [&[u8]; BUTTON_COUNT] = [b"BUTTON A", b"BUTTON B", b"STICK SW"];
+const ROLES: [&[u8]; BUTTON_COUNT] = [b"FIRE", b"BOMB", b"MENU"];
+
+pub enum MenuAction {
+    Calibrate,
+}
+
+// settings menu, the game waits while it is open. up and down pick a
+// line, left, right or fire change it. the menu button closes it
+pub struct Menu {
+    pub open: bool,
+    item: usize,
+    // last stick direction and the frames it has been held
+    dir: (i16, i16),
+    held: u16,
+}
+
+impl Menu {
+    pub fn new() -> Menu {
+        Menu {
+            open: false,
+            item: 0,
+            dir: (0, 0),
+            held: 0,
+        }
+    }
+
+    // once per frame while open, with the stick direction
+    pub fn steer(&mut self, x_move: i16, y_move: i16, settings: &mut Settings) {
+        let dir = (x_move, y_move);
+        if dir != self.dir {
+            self.dir = dir;
+            self.held = 0;
+        } else {
+            self.held = self.held.saturating_add(1);
+            if dir == (0, 0) || self.held < MENU_DELAY || (self.held - MENU_DELAY) % MENU_REPEAT > 0
+            {
+                return;
+            }
+        }
+        if y_move != 0 {
+            let count = MENU_ITEMS.len() as i16;
+            self.item = (self.item as i16 + y_move).rem_euclid(count) as usize;
+        } else if x_move != 0 {
+            self.change(x_move, settings);
+        }
+    }
+
+    // fire on the current line
+    pub fn select(&mut self, settings: &mut Settings) -> Option<MenuAction> {
+        if MENU_ITEMS[self.item] == MenuItem::Calibrate {
+            return Some(MenuAction::Calibrate);
+        }
+        self.change(1, settings);
+        None
+    }
+
+    fn change(&mut self, step: i16, settings: &mut Settings) {
+        let map = &mut settings.input_map;
+        match MENU_ITEMS[self.item] {
+            MenuItem::SwapAxes => map.swap_axes = !map.swap_axes,
+            MenuItem::InvertX => map.invert_x = !map.invert_x,
+            MenuItem::InvertY => map.invert_y = !map.invert_y,
+            // roles that leave no menu button are skipped, the last
+            // menu button keeps its role
+            MenuItem::Map(button) => {
+                let old = *map;
+                for _ in 1..BUTTON_COUNT {
+                    let role = &mut map.buttons[button as usize];
+                    let next = (*role as i16 + step).rem_euclid(BUTTON_COUNT as i16);
+                    *role = BUTTONS[next as usize];
+                    if map.has_menu() {
+                        return;
+                    }
+                }
+                *map = old;
+            }
+            MenuItem::Calibrate => {}
+        }
+    }
+
+    pub fn draw<C: Canvas>(&self, canvas: &mut C, settings: &Settings) {
+        let map = &settings.input_map;
+        let on_off = |on: bool| if on { &b"ON"[..] } else { &b"OFF"[..] };
+        // scrolls on short panels, the current line stays in view
+        let rows = (DISP_H / LINE_H) as usize;
+        let first = (self.item + 1).saturating_sub(rows);
+        for (row, item) in MENU_ITEMS.iter().enumerate().skip(first).take(rows) {
+            let y = (row - first) as i16 * LINE_H;
+            let (label, value): (&[u8], &[u8]) = match *item {
+                MenuItem::SwapAxes => (b"SWAP XY", on_off(map.swap_axes)),
+                MenuItem::InvertX => (b"INVERT X", on_off(map.invert_x)),
+                MenuItem::InvertY => (b"INVERT Y", on_off(map.invert_y)),
+                MenuItem::Map(button) => (
+                    BUTTON_NAMES[button as usize],
+                    ROLES[map.buttons[button as usize] as usize],
+                ),
+                MenuItem::Calibrate => (b"CALIBRATE", b""),
+            };
+            if row == self.item {
+                draw_text(canvas, 0, y, b">", Align::Left);
+            }
+            draw_text(canvas, ADVANCE, y, label, Align::Left);
+            draw_text(canvas, DISP_W - 1, y, value, Align::Right);
+        }
+    }
+}
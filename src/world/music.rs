@@ -0,0 +1,174 @@
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use super::*;
+
+// a note of a pattern. lengths are in 32nd notes, so a quarter is 8
+#[derive(Copy, Clone)]
+pub struct Row {
+    // midi note number, 0 is a rest
+    pub note: u8,
+    pub len: u8,
+}
+
+// patterns are played in the order of the sequence
+pub struct Song {
+    // quarter notes per minute
+    pub tempo: u16,
+    pub duty: u8,
+    pub env: Envelope,
+    pub looped: bool,
+    pub sequence: &'static [u8],
+    pub patterns: &'static [&'static [Row]],
+}
+
+include!(concat!(env!("OUT_DIR"), "/tunes.rs"));
+
+const fn row(note: u8, len: u8) -> Row {
+    Row { note, len }
+}
+
+// in game loop, written by hand
+pub static TUNE_LEVEL: Song = Song {
+    tempo: 120,
+    duty: 8,
+    env: ENV_PLUCK,
+    looped: true,
+    sequence: &[0, 0, 1, 0],
+    patterns: &[
+        // a minor walk
+        &[
+            row(57, 4),
+            row(69, 4),
+            row(57, 4),
+            row(67, 4),
+            row(57, 4),
+            row(64, 4),
+            row(65, 4),
+            row(67, 4),
+        ],
+        // up to c and back
+        &[
+            row(60, 4),
+            row(72, 4),
+            row(60, 4),
+            row(71, 4),
+            row(59, 4),
+            row(0, 4),
+            row(64, 8),
+        ],
+    ],
+};
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Tune {
+    Title,
+    Level,
+    GameOver,
+}
+
+// in the order of Tune
+pub static TUNES: [&Song; 3] = [&TUNE_TITLE, &TUNE_LEVEL, &TUNE_GAMEOVER];
+
+impl Tune {
+    pub fn song(self) -> &'static Song {
+        TUNES[self as usize]
+    }
+}
+
+// octave 8, lower ones are halved
+const OCTAVE_8: [u16; 12] = [
+    4186, 4435, 4699, 4978, 5274, 5588, 5920, 6272, 6645, 7040, 7459, 7902,
+];
+
+pub fn note_freq(note: u8) -> u16 {
+    let octave = (note / 12).min(9);
+    OCTAVE_8[(note % 12) as usize] >> (9 - octave)
+}
+
+// requests from game code to the TIM3 interrupt: 0 nothing,
+// STOP_MUSIC, or the Tune + 1
+static MUSIC_REQUEST: AtomicU8 = AtomicU8::new(0);
+const STOP_MUSIC: u8 = 255;
+static MUSIC_PLAYING: AtomicBool = AtomicBool::new(false);
+
+pub fn play_music(tune: Tune) {
+    MUSIC_PLAYING.store(true, Ordering::Release);
+    MUSIC_REQUEST.store(tune as u8 + 1, Ordering::Release);
+}
+
+pub fn stop_music() {
+    MUSIC_PLAYING.store(false, Ordering::Release);
+    MUSIC_REQUEST.store(STOP_MUSIC, Ordering::Release);
+}
+
+// false once a song that doesn't loop is over
+pub fn music_playing() -> bool {
+    MUSIC_PLAYING.load(Ordering::Acquire)
+}
+
+// steps through a song a note at a time, owned by the audio driver
+pub struct Tracker {
+    song: Option<&'static Song>,
+    // position in the sequence and in the pattern
+    order: usize,
+    row: usize,
+    // silence after the current note, so repeated notes are heard
+    gap: u16,
+}
+
+impl Tracker {
+    pub fn new() -> Tracker {
+        Tracker {
+            song: None,
+            order: 0,
+            row: 0,
+            gap: 0,
+        }
+    }
+
+    // take what game code asked for
+    pub fn poll(&mut self) {
+        match MUSIC_REQUEST.swap(0, Ordering::AcqRel) {
+            0 => {}
+            STOP_MUSIC => self.song = None,
+            n => {
+                *self = Tracker::new();
+                self.song = Some(TUNES[n as usize - 1]);
+            }
+        }
+    }
+
+    pub fn next(&mut self) -> Option<Note> {
+        let song = self.song?;
+
+        if self.gap > 0 {
+            let gap = self.gap;
+            self.gap = 0;
+            return Some(Note::rest(gap));
+        }
+
+        if self.order >= song.sequence.len() {
+            if !song.looped {
+                self.song = None;
+                MUSIC_PLAYING.store(false, Ordering::Release);
+                return None;
+            }
+            self.order = 0;
+        }
+
+        let pattern = song.patterns[song.sequence[self.order] as usize];
+        let row = pattern[self.row];
+        self.row += 1;
+        if self.row >= pattern.len() {
+            self.row = 0;
+            self.order += 1;
+        }
+
+        let ms = (row.len as u32 * 60_000 / (song.tempo as u32 * 8)) as u16;
+        if row.note == 0 {
+            return Some(Note::rest(ms));
+        }
+        self.gap = ms / 8;
+        Some(Note::new(note_freq(row.note), ms - self.gap, song.duty).with_env(song.env))
+    }
+}
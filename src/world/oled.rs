@@ -0,0 +1,46 @@
+use display_interface::{DataFormat::U8, WriteOnlyDataCommand};
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::digital::v2::OutputPin;
+
+use super::*;
+
+// same sequence as the ssd1306 crate reset
+pub fn reset_display<RST: OutputPin, DELAY: DelayMs<u8>>(rst: &mut RST, delay: &mut DELAY) {
+    rst.set_high().ok();
+    delay.delay_ms(1);
+    rst.set_low().ok();
+    delay.delay_ms(10);
+    rst.set_high().ok();
+}
+
+// blocking link over any display-interface bus, used for i2c where
+// there is no DMA path. every write returns when it is sent
+pub struct Oled<DI> {
+    iface: DI,
+}
+
+impl<DI> Oled<DI>
+where
+    DI: WriteOnlyDataCommand,
+{
+    pub fn new(iface: DI) -> Oled<DI> {
+        Oled { iface }
+    }
+}
+
+impl<DI> FlushLink for Oled<DI>
+where
+    DI: WriteOnlyDataCommand,
+{
+    fn command(&mut self, bytes: &[u8]) {
+        self.iface.send_commands(U8(bytes)).ok();
+    }
+
+    fn start_data(&mut self, data: &[u8]) {
+        self.iface.send_data(U8(data)).ok();
+    }
+
+    fn blocking(&self) -> bool {
+        true
+    }
+}
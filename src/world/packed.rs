@@ -0,0 +1,82 @@
+// sprites packed by build.rs from the art in sprites.rs. a frame is
+// w * h bits per plane, row by row, msb first, high plane first
+pub struct PackedFrame {
+    // into PACKED_SPRITES
+    pub offset: u16,
+    // run length encoded, see BitReader
+    pub rle: bool,
+}
+
+pub struct SpriteAsset {
+    pub w: u8,
+    pub h: u8,
+    // 1: plain sprite, 2: gray sprite with a second bitplane
+    pub planes: u8,
+    pub frames: &'static [PackedFrame],
+}
+
+include!(concat!(env!("OUT_DIR"), "/sprites.rs"));
+
+// reads a packed frame one pixel at a time
+#[derive(Copy, Clone)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    rle: bool,
+    // plain: bit index in the byte. rle: pixels left in the run
+    count: u8,
+    value: bool,
+}
+
+impl BitReader<'static> {
+    pub fn new(frame: &PackedFrame) -> BitReader<'static> {
+        BitReader {
+            data: &PACKED_SPRITES[frame.offset as usize..],
+            pos: 0,
+            rle: frame.rle,
+            count: 0,
+            value: false,
+        }
+    }
+}
+
+impl<'a> BitReader<'a> {
+    // frame data that is not rle, like the sprite overrides
+    pub fn plain(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data,
+            pos: 0,
+            rle: false,
+            count: 0,
+            value: false,
+        }
+    }
+
+    pub fn next(&mut self) -> bool {
+        if self.rle {
+            // a run byte has the value on bit 7 and the length - 1 below
+            if self.count == 0 {
+                let byte = self.data[self.pos];
+                self.pos += 1;
+                self.value = byte & 0x80 > 0;
+                self.count = (byte & 0x7F) + 1;
+            }
+            self.count -= 1;
+            self.value
+        } else {
+            let bit = self.data[self.pos] >> (7 - self.count) & 1 > 0;
+            self.count += 1;
+            if self.count == 8 {
+                self.count = 0;
+                self.pos += 1;
+            }
+            bit
+        }
+    }
+
+    pub fn skip(&mut self, pixels: u16) {
+        for _ in 0..pixels {
+            self.next();
+        }
+    }
+}
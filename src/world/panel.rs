@@ -0,0 +1,95 @@
+use super::*;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Controller {
+    Ssd1306,
+    // 132 column ram with the panel in the middle, page addressing only
+    Sh1106,
+}
+
+// the playfield is landscape, so only the hardware flip is offered.
+// quarter turns would need a transposed framebuffer
+#[derive(Copy, Clone, PartialEq)]
+pub enum Rotation {
+    Deg0,
+    Deg180,
+}
+
+#[derive(Copy, Clone)]
+pub struct Panel {
+    pub width: i16,
+    pub height: i16,
+    pub controller: Controller,
+    pub rotation: Rotation,
+}
+
+// picked by the cargo features, the bus is chosen by `i2c`
+pub const PANEL: Panel = Panel {
+    width: 128,
+    height: if cfg!(feature = "display-128x32") {
+        32
+    } else {
+        64
+    },
+    controller: if cfg!(feature = "sh1106") {
+        Controller::Sh1106
+    } else {
+        Controller::Ssd1306
+    },
+    rotation: if cfg!(feature = "rotate-180") {
+        Rotation::Deg180
+    } else {
+        Rotation::Deg0
+    },
+};
+
+impl Panel {
+    // same settings the ssd1306 crate uses, one command per write so
+    // it fits the i2c interface buffer
+    pub fn init<L: FlushLink>(&self, link: &mut L) {
+        let (segment, com_dir) = match self.rotation {
+            Rotation::Deg0 => (0xA1, 0xC8),
+            Rotation::Deg180 => (0xA0, 0xC0),
+        };
+        let com_pins = if self.height > 32 { 0x12 } else { 0x02 };
+
+        link.command(&[0xAE]);
+        link.command(&[0xD5, 0x80]);
+        link.command(&[0xA8, self.height as u8 - 1]);
+        link.command(&[0xD3, 0x00]);
+        link.command(&[0x40]);
+        match self.controller {
+            Controller::Ssd1306 => {
+                // charge pump, horizontal addressing
+                link.command(&[0x8D, 0x14]);
+                link.command(&[0x20, 0x00]);
+            }
+            Controller::Sh1106 => {
+                // dc-dc converter on
+                link.command(&[0xAD, 0x8B]);
+            }
+        }
+        link.command(&[0xDA, com_pins]);
+        link.command(&[segment]);
+        link.command(&[com_dir]);
+        link.command(&[0xD9, 0x21]);
+        link.command(&[0x81, CONTRAST]);
+        link.command(&[0xDB, 0x40]);
+        link.command(&[0xA4]);
+        link.command(&[0xA6]);
+        link.command(&[0xAF]);
+    }
+
+    // commands to write columns first..=last of a page, and how many
+    // bytes of the array are used
+    pub fn window(&self, page: u8, first: u8, last: u8) -> ([u8; 6], usize) {
+        match self.controller {
+            Controller::Ssd1306 => ([0x21, first, last, 0x22, page, page], 6),
+            // the column auto increments inside the page, no end needed
+            Controller::Sh1106 => {
+                let col = first + 2;
+                ([0xB0 | page, col & 0xF, 0x10 | col >> 4, 0, 0, 0], 3)
+            }
+        }
+    }
+}
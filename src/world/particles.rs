@@ -0,0 +1,151 @@
+use super::*;
+
+pub const PARTICLES: usize = 48;
+// positions and speeds are in 1/FIXED px
+pub const FIXED: i16 = 16;
+
+#[derive(Copy, Clone)]
+pub struct Particle {
+    pub x: i16,
+    pub y: i16,
+    pub vx: i16,
+    pub vy: i16,
+    pub gravity: i16,
+    // ticks left, 0 is a free slot
+    pub life: u8,
+    // 1 or 2 px square
+    pub size: u8,
+}
+
+impl Particle {
+    pub fn new() -> Particle {
+        Particle {
+            x: 0,
+            y: 0,
+            vx: 0,
+            vy: 0,
+            gravity: 0,
+            life: 0,
+            size: 1,
+        }
+    }
+}
+
+// how a burst of particles is spawned, speeds in 1/FIXED px per tick
+pub struct Emitter {
+    pub count: u8,
+    pub vx: (i16, i16),
+    pub vy: (i16, i16),
+    pub life: (u8, u8),
+    pub gravity: i16,
+    // chance in 4 of a 2 px particle
+    pub big: u8,
+}
+
+pub const EMIT_EXPLOSION: Emitter = Emitter {
+    count: 12,
+    vx: (-24, 24),
+    vy: (-24, 24),
+    life: (8, 16),
+    gravity: 1,
+    big: 1,
+};
+
+pub const EMIT_IMPACT: Emitter = Emitter {
+    count: 4,
+    vx: (-20, 4),
+    vy: (-16, 16),
+    life: (3, 6),
+    gravity: 0,
+    big: 0,
+};
+
+pub const EMIT_THRUST: Emitter = Emitter {
+    count: 1,
+    vx: (-40, -20),
+    vy: (-4, 4),
+    life: (3, 7),
+    gravity: 0,
+    big: 0,
+};
+
+fn gen_range(random: &mut Xorshift128pState, range: (i16, i16)) -> i16 {
+    if range.1 <= range.0 {
+        return range.0;
+    }
+    random.gen_min_max(0, (range.1 - range.0) as u64) as i16 + range.0
+}
+
+pub struct Particles {
+    pub pool: [Particle; PARTICLES],
+    // when the pool is full the oldest slots are reused
+    next: usize,
+}
+
+impl Particles {
+    pub fn new() -> Particles {
+        Particles {
+            pool: [Particle::new(); PARTICLES],
+            next: 0,
+        }
+    }
+
+    // x, y in screen px
+    pub fn emit(&mut self, random: &mut Xorshift128pState, x: i16, y: i16, emitter: &Emitter) {
+        for _ in 0..emitter.count {
+            let mut particle = Particle::new();
+            particle.x = x * FIXED;
+            particle.y = y * FIXED;
+            particle.vx = gen_range(random, emitter.vx);
+            particle.vy = gen_range(random, emitter.vy);
+            particle.gravity = emitter.gravity;
+            particle.life = gen_range(random, (emitter.life.0 as i16, emitter.life.1 as i16)) as u8;
+            if ((random.gen() % 4) as u8) < emitter.big {
+                particle.size = 2;
+            }
+
+            self.pool[self.next] = particle;
+            self.next = (self.next + 1) % PARTICLES;
+        }
+    }
+
+    pub fn tick(&mut self, clip: (i16, i16)) {
+        for particle in self.pool.iter_mut() {
+            if particle.life == 0 {
+                continue;
+            }
+            particle.life -= 1;
+            particle.vy += particle.gravity;
+            particle.x += particle.vx;
+            particle.y += particle.vy;
+
+            // dies when leaving the play area
+            let (x, y) = (particle.x / FIXED, particle.y / FIXED);
+            if x < 0 || x >= DISP_W || y < clip.0 || y >= clip.1 {
+                particle.life = 0;
+            }
+        }
+    }
+
+    // particles fade out through the gray levels
+    pub fn draw<C: Canvas>(&self, canvas: &mut C, phase: u8) {
+        for particle in self.pool.iter() {
+            let shade = match particle.life {
+                0 => 0,
+                1..=3 => 1,
+                4..=6 => 2,
+                _ => 3,
+            };
+            if shade <= phase {
+                continue;
+            }
+
+            let (x, y) = (particle.x / FIXED, particle.y / FIXED);
+            for dy in 0..particle.size as i16 {
+                for dx in 0..particle.size as i16 {
+                    canvas.set_pixel(x + dx, y + dy, true);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub const QUEUE_SIZE: usize = 128;
+
+// values from an interrupt to the main loop or the other way. only
+// one side may push and only one may pop, unless the popping side
+// is blocked by a critical section
+pub struct Queue<T, const N: usize> {
+    buf: UnsafeCell<[T; N]>,
+    // next slot to write and to read, equal when empty
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// push and pop touch different slots, see above
+unsafe impl<T: Send, const N: usize> Sync for Queue<T, N> {}
+
+impl<T: Copy, const N: usize> Queue<T, N> {
+    // `empty` fills the unused slots
+    pub const fn new(empty: T) -> Queue<T, N> {
+        Queue {
+            buf: UnsafeCell::new([empty; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    // false when full, the value is dropped
+    pub fn push(&self, value: T) -> bool {
+        let head = self.head.load(Ordering::Relaxed);
+        let next = (head + 1) % N;
+        if next == self.tail.load(Ordering::Acquire) {
+            return false;
+        }
+        unsafe { (*self.buf.get())[head] = value };
+        self.head.store(next, Ordering::Release);
+        true
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail == self.head.load(Ordering::Acquire) {
+            return None;
+        }
+        let value = unsafe { (*self.buf.get())[tail] };
+        self.tail.store((tail + 1) % N, Ordering::Release);
+        Some(value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+}
+
+pub type ByteQueue = Queue<u8, QUEUE_SIZE>;
+
+// filled by the USART1 interrupt
+pub static RX_QUEUE: ByteQueue = ByteQueue::new(0);
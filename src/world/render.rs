@@ -0,0 +1,90 @@
+use super::*;
+
+// the oled is monochrome, gray comes from temporal dithering: a pixel
+// of level 0..=3 is lit in `level` of every PHASES frames. needs a
+// steady frame rate to not flicker, see FRAME_HZ
+pub const PHASES: u8 = 3;
+// TIM2 paces the loop to this rate. an unpaced loop runs faster on an
+// empty screen than in a busy one, and the gray levels flicker with
+// it. the bus time the partial flush saves is headroom for the busiest
+// frame, so the rate holds under load
+pub const FRAME_HZ: u32 = 40;
+
+// outline the collision boxes of every entity
+pub const DEBUG_HITBOXES: bool = false;
+
+pub fn draw_entity<C: Canvas>(
+    canvas: &mut C,
+    entity: &Entity,
+    overrides: &SpriteOverrides,
+    phase: u8,
+    clip: (i16, i16),
+) {
+    let (clip_top, clip_bottom) = clip;
+    let asset = &SPRITE_ASSETS[entity.sprite as usize];
+    let (w, h) = (asset.w as i16, asset.h as i16);
+
+    // uploaded frames win over the flash ones
+    let mut hi = match overrides.get(entity.sprite, entity.frame) {
+        Some(data) => BitReader::plain(data),
+        None => BitReader::new(&asset.frames[entity.frame as usize]),
+    };
+
+    // the low plane pixels follow the high plane ones
+    let mut lo = hi;
+    if asset.planes == 2 {
+        lo.skip((w * h) as u16);
+    }
+
+    for y in 0..h {
+        let y_pos = y + entity.y;
+        // clipped rows are still decoded, the readers only go forward
+        let visible = y_pos >= clip_top && y_pos < clip_bottom;
+
+        for x in 0..w {
+            let level = if asset.planes == 2 {
+                (hi.next() as u8) << 1 | lo.next() as u8
+            } else if hi.next() {
+                entity.shade
+            } else {
+                0
+            };
+
+            if visible && level > phase {
+                // print pixel
+                canvas.set_pixel(x + entity.x, y_pos, true);
+            }
+        }
+    }
+}
+
+pub fn draw_world<C: Canvas>(
+    canvas: &mut C,
+    world: &World,
+    overrides: &SpriteOverrides,
+    phase: u8,
+) {
+    // entities stay out of the hud band
+    let clip = world.hud.play_area();
+
+    // screen shake
+    let mut canvas = Shifted {
+        canvas,
+        offset: world.effects.offset(),
+        clip,
+    };
+
+    world.background.draw(&mut canvas, phase, clip);
+
+    for i in 0..POOL_SIZE {
+        let entity = world.entities[i];
+        if !entity.del {
+            draw_entity(&mut canvas, &entity, overrides, phase, clip);
+            if DEBUG_HITBOXES {
+                canvas.rect(entity.x, entity.y, entity.w, entity.h, true);
+            }
+        }
+    }
+
+    world.particles.draw(&mut canvas, phase);
+}
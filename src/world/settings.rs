@@ -0,0 +1,145 @@
+use super::*;
+
+// contrast register value of each brightness level, the middle one
+// is the ssd1306 default
+pub const CONTRAST_LEVELS: [u8; 5] = [0x08, 0x2F, CONTRAST, 0x9F, 0xFF];
+// percent of the note duty at each volume level
+pub const VOLUME_LEVELS: [u8; 5] = [10, 25, 50, 75, 100];
+
+pub const DIFFICULTIES: u8 = 3;
+
+// bytes of the stored settings. new fields go at the end, older
+// records are shorter and the missing fields keep their default
+#[cfg(target_os = "none")]
+const SETTINGS_BYTES: usize = 4 + CALIBRATION_BYTES + 2 + INPUT_MAP_BYTES;
+
+// user preferences, they outlive a game
+#[derive(Copy, Clone)]
+pub struct Settings {
+    // index in CONTRAST_LEVELS
+    pub brightness: u8,
+    // music keeps playing under the sound effects
+    pub polyphony: bool,
+    // index in VOLUME_LEVELS
+    pub volume: u8,
+    pub muted: bool,
+    pub calibration: Calibration,
+    // 0 easy, 1 normal, 2 hard
+    pub difficulty: u8,
+    // index in WEAPONS
+    pub weapon: u8,
+    // for the board controls only, a phone sends what it means
+    pub input_map: InputMap,
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings {
+            brightness: 2,
+            polyphony: false,
+            volume: VOLUME_LEVELS.len() as u8 - 1,
+            muted: false,
+            calibration: Calibration::new(),
+            difficulty: 1,
+            weapon: 0,
+            input_map: InputMap::new(),
+        }
+    }
+
+    // the saved settings, or the defaults on a fresh chip
+    #[cfg(target_os = "none")]
+    pub fn load() -> Settings {
+        let mut settings = Settings::new();
+        let mut bytes = [0u8; SETTINGS_BYTES];
+        if let Some(len) = storage_load(&mut bytes) {
+            settings.read(&bytes[..len]);
+        }
+        settings
+    }
+
+    #[cfg(target_os = "none")]
+    pub fn save(&self) -> Result<(), &'static str> {
+        storage_save(&self.bytes())
+    }
+
+    #[cfg(target_os = "none")]
+    fn bytes(&self) -> [u8; SETTINGS_BYTES] {
+        let mut bytes = [0u8; SETTINGS_BYTES];
+        bytes[..4].copy_from_slice(&[
+            self.brightness,
+            self.polyphony as u8,
+            self.volume,
+            self.muted as u8,
+        ]);
+        bytes[4..4 + CALIBRATION_BYTES].copy_from_slice(&self.calibration.bytes());
+        bytes[4 + CALIBRATION_BYTES] = self.difficulty;
+        bytes[5 + CALIBRATION_BYTES] = self.weapon;
+        bytes[6 + CALIBRATION_BYTES..].copy_from_slice(&self.input_map.bytes());
+        bytes
+    }
+
+    #[cfg(target_os = "none")]
+    fn read(&mut self, bytes: &[u8]) {
+        let mut field = bytes.iter().copied();
+        if let Some(b) = field.next() {
+            self.set_brightness(b);
+        }
+        if let Some(b) = field.next() {
+            self.polyphony = b != 0;
+        }
+        if let Some(b) = field.next() {
+            self.set_volume(b);
+        }
+        if let Some(b) = field.next() {
+            self.muted = b != 0;
+        }
+        if let Some(cal) = bytes.get(4..).and_then(Calibration::from_bytes) {
+            self.calibration = cal;
+        }
+        if let Some(&b) = bytes.get(4 + CALIBRATION_BYTES) {
+            self.set_difficulty(b);
+        }
+        if let Some(&b) = bytes.get(5 + CALIBRATION_BYTES) {
+            self.set_weapon(b);
+        }
+        if let Some(map) = bytes
+            .get(6 + CALIBRATION_BYTES..)
+            .and_then(InputMap::from_bytes)
+        {
+            self.input_map = map;
+        }
+    }
+
+    pub fn set_brightness(&mut self, level: u8) {
+        self.brightness = level.min(CONTRAST_LEVELS.len() as u8 - 1);
+    }
+
+    pub fn contrast(&self) -> u8 {
+        CONTRAST_LEVELS[self.brightness as usize]
+    }
+
+    pub fn set_difficulty(&mut self, level: u8) {
+        self.difficulty = level.min(DIFFICULTIES - 1);
+    }
+
+    pub fn set_weapon(&mut self, weapon: u8) {
+        self.weapon = weapon.min(WEAPON_COUNT - 1);
+    }
+
+    pub fn weapon(&self) -> &'static Weapon {
+        &WEAPONS[self.weapon as usize]
+    }
+
+    pub fn set_volume(&mut self, level: u8) {
+        self.volume = level.min(VOLUME_LEVELS.len() as u8 - 1);
+    }
+
+    // percent for the audio driver, 0 when muted
+    pub fn volume_percent(&self) -> u8 {
+        if self.muted {
+            0
+        } else {
+            VOLUME_LEVELS[self.volume as usize]
+        }
+    }
+}
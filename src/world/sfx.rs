@@ -0,0 +1,185 @@
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use super::*;
+
+// a piece of a sound effect, frequencies in Hz
+#[derive(Copy, Clone)]
+pub enum Step {
+    Tone {
+        freq: u16,
+        ms: u16,
+    },
+    Rest {
+        ms: u16,
+    },
+    // slides in `steps` notes
+    Sweep {
+        from: u16,
+        to: u16,
+        ms: u16,
+        steps: u8,
+    },
+    // cycles fast through the notes of a chord
+    Arpeggio {
+        freqs: &'static [u16],
+        note_ms: u16,
+        ms: u16,
+    },
+}
+
+pub struct SfxDef {
+    pub steps: &'static [Step],
+    // every note gets up to this many Hz added or taken
+    pub jitter: u16,
+    // times the steps are played
+    pub repeat: u8,
+    pub duty: u8,
+    // applied to every note
+    pub env: Envelope,
+    // an effect can't cut one of higher priority
+    pub priority: u8,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Sfx {
+    Shoot,
+    Explode,
+    Hit,
+    Bomb,
+    LevelUp,
+}
+
+// in the order of Sfx
+pub const SFX_TABLE: [SfxDef; 5] = [
+    // shoot
+    SfxDef {
+        steps: &[
+            Step::Tone { freq: 3550, ms: 25 },
+            Step::Tone { freq: 2900, ms: 25 },
+        ],
+        jitter: 150,
+        repeat: 1,
+        duty: 10,
+        env: ENV_PLUCK,
+        priority: 1,
+    },
+    // explode
+    SfxDef {
+        steps: &[
+            Step::Tone { freq: 800, ms: 25 },
+            Step::Tone { freq: 900, ms: 25 },
+            Step::Tone { freq: 1000, ms: 25 },
+        ],
+        jitter: 30,
+        repeat: 1,
+        duty: 10,
+        env: ENV_PLUCK,
+        priority: 2,
+    },
+    // hit
+    SfxDef {
+        steps: &[Step::Sweep {
+            from: 1200,
+            to: 150,
+            ms: 300,
+            steps: 12,
+        }],
+        jitter: 80,
+        repeat: 1,
+        duty: 30,
+        env: ENV_FLAT,
+        priority: 4,
+    },
+    // bomb
+    SfxDef {
+        steps: &[
+            Step::Sweep {
+                from: 200,
+                to: 1600,
+                ms: 80,
+                steps: 4,
+            },
+            Step::Rest { ms: 20 },
+        ],
+        jitter: 200,
+        repeat: 3,
+        duty: 40,
+        env: ENV_SWELL,
+        priority: 3,
+    },
+    // level up
+    SfxDef {
+        steps: &[Step::Arpeggio {
+            freqs: &[1047, 1319, 1568],
+            note_ms: 30,
+            ms: 270,
+        }],
+        jitter: 0,
+        repeat: 1,
+        duty: 20,
+        env: ENV_PLUCK,
+        priority: 3,
+    },
+];
+
+impl Sfx {
+    pub fn def(self) -> &'static SfxDef {
+        &SFX_TABLE[self as usize]
+    }
+}
+
+fn jittered(random: &mut Xorshift128pState, freq: u16, jitter: u16) -> u16 {
+    if jitter == 0 {
+        return freq;
+    }
+    let offset = random.gen_min_max(0, jitter as u64 * 2 + 1) as i32 - jitter as i32;
+    (freq as i32 + offset).max(1) as u16
+}
+
+// priority of the effect playing, 0 when none. cleared by the
+// audio driver
+pub static SFX_PRIORITY: AtomicU8 = AtomicU8::new(0);
+
+// replaces the effect playing unless that one has a higher priority.
+// notes past the audio queue size are dropped, keep the effects short
+pub fn play_sfx(sfx: Sfx, random: &mut Xorshift128pState) {
+    let def = sfx.def();
+    if def.priority < SFX_PRIORITY.load(Ordering::Acquire) {
+        return;
+    }
+    stop_notes();
+
+    let tone = |random: &mut Xorshift128pState, freq: u16, ms: u16| {
+        play_note(Note::new(jittered(random, freq, def.jitter), ms, def.duty).with_env(def.env));
+    };
+
+    for _ in 0..def.repeat {
+        for step in def.steps {
+            match *step {
+                Step::Tone { freq, ms } => tone(random, freq, ms),
+                Step::Rest { ms } => {
+                    play_note(Note::rest(ms));
+                }
+                Step::Sweep {
+                    from,
+                    to,
+                    ms,
+                    steps,
+                } => {
+                    let steps = steps.max(1) as i32;
+                    for i in 0..steps {
+                        let freq = from as i32 + (to as i32 - from as i32) * i / steps;
+                        tone(random, freq as u16, ms / steps as u16);
+                    }
+                }
+                Step::Arpeggio { freqs, note_ms, ms } => {
+                    for i in 0..(ms / note_ms.max(1)) as usize {
+                        tone(random, freqs[i % freqs.len()], note_ms);
+                    }
+                }
+            }
+        }
+    }
+    // after the notes, so the driver can't see an empty queue and clear it
+    SFX_PRIORITY.store(def.priority, Ordering::Release);
+}
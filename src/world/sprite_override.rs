@@ -0,0 +1,80 @@
+use super::*;
+
+// frames that can be replaced at the same time
+pub const OVERRIDES: usize = 4;
+
+// bytes of a frame in the plain packed format
+pub fn frame_bytes(asset: &SpriteAsset) -> usize {
+    (asset.w as usize * asset.h as usize * asset.planes as usize + 7) / 8
+}
+
+#[derive(Copy, Clone)]
+pub struct SpriteOverride {
+    pub sprite: u8,
+    pub frame: u8,
+    // plain packed, like a PackedFrame that is not rle
+    pub data: [u8; MAX_FRAME_BYTES],
+}
+
+// frames uploaded at runtime, drawn instead of the flash ones. lives
+// outside the world, so a game over keeps them
+pub struct SpriteOverrides {
+    slots: [Option<SpriteOverride>; OVERRIDES],
+}
+
+impl SpriteOverrides {
+    pub fn new() -> SpriteOverrides {
+        SpriteOverrides {
+            slots: [None; OVERRIDES],
+        }
+    }
+
+    pub fn get(&self, sprite: u8, frame: u8) -> Option<&[u8]> {
+        self.slots
+            .iter()
+            .flatten()
+            .find(|slot| slot.sprite == sprite && slot.frame == frame)
+            .map(|slot| &slot.data[..])
+    }
+
+    pub fn set(&mut self, sprite: u8, frame: u8, data: &[u8]) -> Result<(), &'static str> {
+        let asset = SPRITE_ASSETS.get(sprite as usize).ok_or("SPRITE")?;
+        if frame as usize >= asset.frames.len() {
+            return Err("FRAME");
+        }
+        if data.len() != frame_bytes(asset) {
+            return Err("SIZE");
+        }
+
+        // replace the same frame or take a free slot
+        let index = self
+            .slots
+            .iter()
+            .position(|slot| match slot {
+                Some(slot) => slot.sprite == sprite && slot.frame == frame,
+                None => false,
+            })
+            .or_else(|| self.slots.iter().position(|slot| slot.is_none()))
+            .ok_or("FULL")?;
+
+        let mut slot = SpriteOverride {
+            sprite,
+            frame,
+            data: [0; MAX_FRAME_BYTES],
+        };
+        slot.data[..data.len()].copy_from_slice(data);
+        self.slots[index] = Some(slot);
+        Ok(())
+    }
+
+    // back to the flash frame
+    pub fn reset(&mut self, sprite: u8, frame: u8) {
+        for slot in self.slots.iter_mut() {
+            if let Some(s) = slot {
+                if s.sprite == sprite && s.frame == frame {
+                    *slot = None;
+                }
+            }
+        }
+    }
+}
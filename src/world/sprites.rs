@@ -1,4 +1,6 @@
-pub const SPRITES: [u32; 71] = [
+// source art, not compiled. build.rs packs it into PACKED_SPRITES,
+// see the asset list there. a row is a u32, bit 31 is the left pixel
+pub const SPRITES: [u32; 79] = [
     // numbers
     0b_01111000_00011000_01111000_01111100, //  0
     0b_11000100_01111000_11001100_11000110, //  1
@@ -75,4 +77,13 @@ pub const SPRITES: [u32; 71] = [
     0b10100010101000000000000000010000, // 68
     0b10100000101000000001010000010100, // 69
     0b00100001100000000000100000011000, // 70
+    // explosion low plane, drawn dim around the high plane above
+    0b01010101010000000000010101011100, // 71
+    0b11101010111000000000101111101000, // 72
+    0b01110101010000000001011101110100, // 73
+    0b11101110111000000000101000101000, // 74
+    0b11011101010000000001010001111100, // 75
+    0b10101010101000000000101000111000, // 76
+    0b11110101111000000001010000010100, // 77
+    0b10100011101000000000101000111000, // 78
 ];
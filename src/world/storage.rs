@@ -0,0 +1,101 @@
+// the last flash page, kept out of the program by memory.x. it holds
+// one record: magic, payload length, payload, checksum
+use super::*;
+
+pub const STORAGE_ADDR: u32 = 0x0800_FC00;
+pub const RECORD_MAX: usize = 64;
+
+const MAGIC: [u8; 2] = *b"SV";
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+
+fn read_byte(offset: usize) -> u8 {
+    unsafe { core::ptr::read_volatile((STORAGE_ADDR as usize + offset) as *const u8) }
+}
+
+fn checksum(payload: &[u8]) -> u8 {
+    payload.iter().fold(0x5Au8, |sum, b| sum.rotate_left(1) ^ b)
+}
+
+// copies the stored payload into `buf`, returns its length. None when
+// the page is erased or the record is broken
+pub fn storage_load(buf: &mut [u8]) -> Option<usize> {
+    if read_byte(0) != MAGIC[0] || read_byte(1) != MAGIC[1] {
+        return None;
+    }
+    let len = read_byte(2) as usize;
+    if len > RECORD_MAX - 4 {
+        return None;
+    }
+    let mut payload = [0u8; RECORD_MAX];
+    for (i, b) in payload[..len].iter_mut().enumerate() {
+        *b = read_byte(3 + i);
+    }
+    if checksum(&payload[..len]) != read_byte(3 + len) {
+        return None;
+    }
+    let len = len.min(buf.len());
+    buf[..len].copy_from_slice(&payload[..len]);
+    Some(len)
+}
+
+fn wait(flash: &pac::flash::RegisterBlock) -> Result<(), &'static str> {
+    while flash.sr.read().bsy().bit_is_set() {}
+    let sr = flash.sr.read();
+    let failed = sr.pgerr().bit_is_set() || sr.wrprterr().bit_is_set();
+    // the error and end flags are cleared by writing 1
+    flash
+        .sr
+        .write(|w| w.eop().set_bit().pgerr().set_bit().wrprterr().set_bit());
+    if failed {
+        Err("FLASH")
+    } else {
+        Ok(())
+    }
+}
+
+fn erase_and_program(flash: &pac::flash::RegisterBlock, record: &[u8]) -> Result<(), &'static str> {
+    flash.cr.modify(|_, w| w.per().set_bit());
+    flash.ar.write(|w| unsafe { w.far().bits(STORAGE_ADDR) });
+    flash.cr.modify(|_, w| w.strt().set_bit());
+    let erased = wait(flash);
+    flash.cr.modify(|_, w| w.per().clear_bit());
+    erased?;
+
+    // the flash is written a half word at a time
+    flash.cr.modify(|_, w| w.pg().set_bit());
+    let mut result = Ok(());
+    for (i, pair) in record.chunks(2).enumerate() {
+        let half = pair[0] as u16 | (*pair.get(1).unwrap_or(&0xFF) as u16) << 8;
+        let addr = (STORAGE_ADDR as usize + i * 2) as *mut u16;
+        unsafe { core::ptr::write_volatile(addr, half) };
+        result = wait(flash);
+        if result.is_err() {
+            break;
+        }
+    }
+    flash.cr.modify(|_, w| w.pg().clear_bit());
+    result
+}
+
+// replaces the stored record. the cpu stalls on the flash for ~30 ms,
+// don't call it every frame
+pub fn storage_save(payload: &[u8]) -> Result<(), &'static str> {
+    if payload.len() > RECORD_MAX - 4 {
+        return Err("SIZE");
+    }
+    let mut record = [0u8; RECORD_MAX];
+    record[..2].copy_from_slice(&MAGIC);
+    record[2] = payload.len() as u8;
+    record[3..3 + payload.len()].copy_from_slice(payload);
+    record[3 + payload.len()] = checksum(payload);
+
+    // the HAL owns the flash for its wait states only, so this goes
+    // straight to the registers
+    let flash = unsafe { &*pac::FLASH::ptr() };
+    flash.keyr.write(|w| unsafe { w.key().bits(KEY1) });
+    flash.keyr.write(|w| unsafe { w.key().bits(KEY2) });
+    let result = erase_and_program(flash, &record[..payload.len() + 4]);
+    flash.cr.modify(|_, w| w.lock().set_bit());
+    result
+}
@@ -0,0 +1,227 @@
+use super::Canvas;
+
+pub const GLYPH_W: i16 = 5;
+pub const GLYPH_H: i16 = 7;
+// glyph width plus one column of spacing
+pub const ADVANCE: i16 = 6;
+// glyph height plus one row of spacing
+pub const LINE_H: i16 = 8;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+// 5x7 ascii font, from ' ' (32) to '~' (126)
+// each glyph is 5 columns, bit 0 is the top row
+pub const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x00, 0x08, 0x14, 0x22, 0x41], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x41, 0x22, 0x14, 0x08, 0x00], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3E, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x04, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x7F, 0x20, 0x18, 0x20, 0x7F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x00, 0x7F, 0x41, 0x41], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x41, 0x41, 0x7F, 0x00, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x08, 0x14, 0x54, 0x54, 0x3C], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x00, 0x7F, 0x10, 0x28, 0x44], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+pub fn draw_char<C: Canvas>(canvas: &mut C, x: i16, y: i16, c: u8) {
+    // unknown characters are drawn as '?'
+    let glyph = if (32..=126).contains(&c) {
+        &FONT[(c - 32) as usize]
+    } else {
+        &FONT[(b'?' - 32) as usize]
+    };
+    draw_glyph(canvas, x, y, glyph);
+}
+
+// one glyph in the FONT column format
+pub fn draw_glyph<C: Canvas>(canvas: &mut C, x: i16, y: i16, glyph: &[u8; 5]) {
+    canvas.blit(x, y, GLYPH_W, GLYPH_H, glyph);
+}
+
+pub fn text_width(s: &[u8]) -> i16 {
+    if s.is_empty() {
+        return 0;
+    }
+    // no spacing after the last glyph
+    s.len() as i16 * ADVANCE - 1
+}
+
+// x is the left edge, center or right edge depending on align
+pub fn draw_text<C: Canvas>(canvas: &mut C, x: i16, y: i16, s: &[u8], align: Align) {
+    let mut x = match align {
+        Align::Left => x,
+        Align::Center => x - text_width(s) / 2,
+        Align::Right => x - text_width(s),
+    };
+    for c in s {
+        draw_char(canvas, x, y, *c);
+        x += ADVANCE;
+    }
+}
+
+// word wraps the text inside a box of width w, also breaking on '\n'
+// words longer than a line are split. returns the height used
+pub fn draw_text_wrapped<C: Canvas>(
+    canvas: &mut C,
+    x: i16,
+    y: i16,
+    w: i16,
+    s: &[u8],
+    align: Align,
+) -> i16 {
+    let max_chars = ((w + 1) / ADVANCE).max(1) as usize;
+    let line_x = match align {
+        Align::Left => x,
+        Align::Center => x + w / 2,
+        Align::Right => x + w,
+    };
+
+    let mut line_y = y;
+    let mut rest = s;
+    loop {
+        let (line, next) = next_line(rest, max_chars);
+        draw_text(canvas, line_x, line_y, line, align);
+        line_y += LINE_H;
+        match next {
+            Some(next) => rest = next,
+            None => break,
+        }
+    }
+    line_y - y
+}
+
+// split the next line of at most max_chars from s
+fn next_line(s: &[u8], max_chars: usize) -> (&[u8], Option<&[u8]>) {
+    if let Some(nl) = s.iter().take(max_chars + 1).position(|c| *c == b'\n') {
+        return (&s[..nl], Some(&s[nl + 1..]));
+    }
+    if s.len() <= max_chars {
+        return (s, None);
+    }
+
+    // break at the last space that fits, or split the word
+    let (end, skip) = match s[..=max_chars].iter().rposition(|c| *c == b' ') {
+        Some(0) | None => (max_chars, 0),
+        Some(space) => (space, 1),
+    };
+    (&s[..end], Some(&s[end + skip..]))
+}
+
+// write the decimal digits of n right aligned in buf, left padded
+// with zeros up to min_digits. returns the used part of buf
+pub fn format_number(buf: &mut [u8; 10], mut n: u32, min_digits: usize) -> &[u8] {
+    let mut start = buf.len();
+    loop {
+        start -= 1;
+        buf[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 && buf.len() - start >= min_digits.min(buf.len()) {
+            break;
+        }
+    }
+    &buf[start..]
+}
+
+pub fn draw_number<C: Canvas>(
+    canvas: &mut C,
+    x: i16,
+    y: i16,
+    n: u32,
+    min_digits: usize,
+    align: Align,
+) {
+    let mut buf = [0u8; 10];
+    let digits = format_number(&mut buf, n, min_digits);
+    draw_text(canvas, x, y, digits, align);
+}
@@ -0,0 +1,173 @@
+use super::*;
+
+pub const TILE: i16 = 8;
+// scroll positions are in 1/SUBPIXEL px, so far layers can move slower than 1 px per tick
+pub const SUBPIXEL: i32 = 16;
+
+// 8x8 tile atlas, 8 columns each, bit 0 is the top row
+pub const TILES: [[u8; 8]; 10] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // 0 empty
+    [0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00], // 1 star
+    [0x00, 0x08, 0x00, 0x2A, 0x00, 0x08, 0x00, 0x00], // 2 big star
+    [0x00, 0x02, 0x20, 0x00, 0x00, 0x08, 0x80, 0x00], // 3 dust
+    [0x00, 0xE0, 0x78, 0x0C, 0x64, 0x62, 0x02, 0x62], // 4 planet top left
+    [0x62, 0x62, 0x62, 0x64, 0x0C, 0x78, 0xE0, 0x00], // 5 planet top right
+    [0x00, 0x07, 0x1C, 0x30, 0x24, 0x40, 0x44, 0x40], // 6 planet bottom left
+    [0x44, 0x40, 0x44, 0x20, 0x34, 0x18, 0x07, 0x00], // 7 planet bottom right
+    [0x00, 0x1C, 0x3E, 0x76, 0x7E, 0x3C, 0x18, 0x00], // 8 asteroid
+    [0x00, 0x00, 0x18, 0x1C, 0x1C, 0x08, 0x00, 0x00], // 9 rock
+];
+
+// maps are row major tile indexes, they wrap around when scrolled.
+// 7 rows fill the play area under the hud on a 64 px panel, shorter
+// ones see the bottom rows
+pub const MAP_FAR: [u8; 224] = [
+    0, 0, 0, 0, 0, 3, 0, 0, 3, 0, 0, 0, 0, 0, 3, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0,
+    0, //
+    0, 0, 0, 3, 0, 0, 4, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, //
+    0, 0, 0, 3, 0, 0, 6, 7, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, //
+    0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0,
+    0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0,
+    0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, //
+];
+
+pub const MAP_STARS: [u8; 112] = [
+    0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 2, 0, //
+    0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, //
+    0, 0, 1, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 2, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+];
+
+pub const MAP_BELT: [u8; 168] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 9, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    9, 9, 9, 0, 0, 9, 0, 0, 9, 0, 0, 0, 8, 0, 0, 0, 9, 9, 0, 0, 9, 0, 9, 0, //
+];
+
+#[derive(Copy, Clone)]
+pub struct Layer {
+    pub map: &'static [u8],
+    pub cols: i16,
+    pub rows: i16,
+    // position and speed in subpixels
+    pub scroll_x: i32,
+    pub scroll_y: i32,
+    pub speed_x: i32,
+    pub speed_y: i32,
+    // gray level 0..=3, far layers are dimmer
+    pub shade: u8,
+}
+
+impl Layer {
+    pub fn new(map: &'static [u8], cols: i16, speed_x: i32, shade: u8) -> Layer {
+        Layer {
+            map,
+            cols,
+            rows: map.len() as i16 / cols,
+            scroll_x: 0,
+            scroll_y: 0,
+            speed_x,
+            speed_y: 0,
+            shade,
+        }
+    }
+
+    // start with the last row on the bottom of a `height` px area
+    pub fn align_bottom(&mut self, height: i16) {
+        let unseen = (self.rows * TILE - height).max(0);
+        self.scroll_y = unseen as i32 * SUBPIXEL;
+    }
+
+    pub fn scroll(&mut self) {
+        let w = self.cols as i32 * TILE as i32 * SUBPIXEL;
+        let h = self.rows as i32 * TILE as i32 * SUBPIXEL;
+        self.scroll_x = (self.scroll_x + self.speed_x).rem_euclid(w);
+        self.scroll_y = (self.scroll_y + self.speed_y).rem_euclid(h);
+    }
+
+    // the map is laid from the top of the clip area
+    pub fn draw<C: Canvas>(&self, canvas: &mut C, phase: u8, clip: (i16, i16)) {
+        if self.shade <= phase {
+            return;
+        }
+        let (clip_top, clip_bottom) = clip;
+        let px = (self.scroll_x / SUBPIXEL) as i16;
+        let py = (self.scroll_y / SUBPIXEL) as i16;
+
+        // one extra tile for the partially visible ones
+        for ty in 0..=(clip_bottom - clip_top) / TILE {
+            let row = (py / TILE + ty) % self.rows;
+            let y0 = clip_top + ty * TILE - py % TILE;
+
+            for tx in 0..=DISP_W / TILE {
+                let col = (px / TILE + tx) % self.cols;
+                let tile = self.map[(row * self.cols + col) as usize];
+                if tile == 0 {
+                    continue;
+                }
+                let x0 = tx * TILE - px % TILE;
+
+                for (x, bits) in TILES[tile as usize].iter().enumerate() {
+                    for y in 0..TILE {
+                        let y_pos = y0 + y;
+                        if bits >> y & 1 > 0 && y_pos >= clip_top && y_pos < clip_bottom {
+                            canvas.set_pixel(x0 + x as i16, y_pos, true);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// parallax layers, drawn back to front
+pub const LAYERS: usize = 3;
+
+pub struct Background {
+    pub layers: [Layer; LAYERS],
+}
+
+impl Background {
+    // `height` of the play area
+    pub fn new(height: i16) -> Background {
+        let mut background = Background {
+            layers: [
+                // dust and a planet, a quarter of a pixel per tick
+                Layer::new(&MAP_FAR, 32, SUBPIXEL / 4, 1),
+                Layer::new(&MAP_STARS, 16, SUBPIXEL * 2, 2),
+                // asteroid belt in front
+                Layer::new(&MAP_BELT, 24, SUBPIXEL * 5, 3),
+            ],
+        };
+        for layer in background.layers.iter_mut() {
+            layer.align_bottom(height);
+        }
+        background
+    }
+
+    pub fn scroll(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.scroll();
+        }
+    }
+
+    pub fn draw<C: Canvas>(&self, canvas: &mut C, phase: u8, clip: (i16, i16)) {
+        for layer in self.layers.iter() {
+            layer.draw(canvas, phase, clip);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+// host only: renders the Mixer, the same one the TIM3 interrupt runs,
+// as the square wave the buzzer would make. the samples go to a
+// callback, which can write a wav file (see wav_header) or feed a
+// live host audio sink. queue effects and music as usual, then render
+use super::*;
+
+pub const SAMPLE_RATE: u32 = 48_000;
+// one Mixer tick
+const SAMPLES_PER_MS: u32 = SAMPLE_RATE / AUDIO_HZ;
+// 8 bit unsigned samples, not full scale to leave some headroom. a
+// sample partly high lies in between
+const HIGH: u8 = 0xC0;
+const LOW: u8 = 0x40;
+const SILENT: u8 = 0x80;
+
+pub struct WavRecorder {
+    mixer: Mixer,
+    // position in the period, in 1 / SAMPLE_RATE of it
+    phase: u32,
+    samples: u32,
+}
+
+impl WavRecorder {
+    pub fn new() -> WavRecorder {
+        WavRecorder {
+            mixer: Mixer::new(),
+            phase: 0,
+            samples: 0,
+        }
+    }
+
+    // samples rendered so far, for wav_header
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    // renders `ms` of audio, one ms of samples per call of `out`
+    pub fn render<F: FnMut(&[u8])>(&mut self, ms: u32, mut out: F) {
+        let mut buf = [SILENT; SAMPLES_PER_MS as usize];
+        for _ in 0..ms {
+            match self.mixer.tick() {
+                Some((note, duty)) if duty > 0 => {
+                    // high for the first `duty` per mille of the period.
+                    // a sample is the part of its time the pin is high,
+                    // so short pulses keep their duty instead of
+                    // snapping to whole samples
+                    let high = SAMPLE_RATE / 1000 * duty;
+                    let step = note.freq as u32;
+                    for sample in buf.iter_mut() {
+                        let end = self.phase + step;
+                        let on = overlap((self.phase, end), (0, high))
+                            + overlap((self.phase, end), (SAMPLE_RATE, SAMPLE_RATE + high));
+                        *sample = LOW + (((HIGH - LOW) as u32 * on + step / 2) / step) as u8;
+                        self.phase = end % SAMPLE_RATE;
+                    }
+                }
+                _ => {
+                    buf = [SILENT; SAMPLES_PER_MS as usize];
+                    self.phase = 0;
+                }
+            }
+            out(&buf);
+            self.samples += SAMPLES_PER_MS;
+        }
+    }
+}
+
+// length of the common part of two [start, end) ranges
+fn overlap(a: (u32, u32), b: (u32, u32)) -> u32 {
+    a.1.min(b.1).saturating_sub(a.0.max(b.0))
+}
+
+// header of a mono 8 bit wav holding `samples` samples
+pub fn wav_header(samples: u32) -> [u8; 44] {
+    let mut header = [0u8; 44];
+    let mut put = |at: usize, bytes: &[u8]| header[at..at + bytes.len()].copy_from_slice(bytes);
+    put(0, b"RIFF");
+    put(4, &(36 + samples).to_le_bytes());
+    put(8, b"WAVEfmt ");
+    put(16, &16u32.to_le_bytes());
+    // pcm, 1 channel
+    put(20, &1u16.to_le_bytes());
+    put(22, &1u16.to_le_bytes());
+    put(24, &SAMPLE_RATE.to_le_bytes());
+    // bytes per second, bytes per sample, bits per sample
+    put(28, &SAMPLE_RATE.to_le_bytes());
+    put(32, &1u16.to_le_bytes());
+    put(34, &8u16.to_le_bytes());
+    put(36, b"data");
+    put(40, &samples.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::Ordering;
+    use std::sync::{Mutex, MutexGuard};
+    use std::vec::Vec;
+
+    // the note queue and the volume are shared, one render at a time
+    static AUDIO: Mutex<()> = Mutex::new(());
+
+    fn lock() -> MutexGuard<'static, ()> {
+        AUDIO.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // queues the effect, returns its notes and `ms` of samples
+    fn render_sfx(sfx: Sfx, ms: u32) -> (Vec<Note>, Vec<u8>) {
+        stop_music();
+        set_volume(100);
+        SFX_PRIORITY.store(0, Ordering::Release);
+        play_sfx(sfx, &mut Xorshift128pState::new(7));
+
+        let mut notes = Vec::new();
+        while let Some(note) = NOTE_QUEUE.pop() {
+            notes.push(note);
+        }
+        for &note in &notes {
+            NOTE_QUEUE.push(note);
+        }
+
+        let mut recorder = WavRecorder::new();
+        let mut samples = Vec::new();
+        recorder.render(ms, |buf| samples.extend_from_slice(buf));
+        (notes, samples)
+    }
+
+    // the samples of ms `from..to`
+    fn window(samples: &[u8], from: u32, to: u32) -> &[u8] {
+        &samples[(from * SAMPLES_PER_MS) as usize..(to * SAMPLES_PER_MS) as usize]
+    }
+
+    // a half high sample reads as SILENT too, a silent ms has nothing
+    // else
+    fn sounding(samples: &[u8]) -> bool {
+        samples
+            .chunks(SAMPLES_PER_MS as usize)
+            .all(|ms| ms.iter().any(|&s| s != SILENT))
+    }
+
+    fn silent(samples: &[u8]) -> bool {
+        samples.iter().all(|&s| s == SILENT)
+    }
+
+    // the whole periods in the samples, and how many
+    fn periods(samples: &[u8]) -> (&[u8], u32) {
+        let starts: Vec<usize> = (1..samples.len())
+            .filter(|&i| samples[i - 1] == LOW && samples[i] > LOW)
+            .collect();
+        let (first, last) = (starts[0], starts[starts.len() - 1]);
+        (&samples[first..last], starts.len() as u32 - 1)
+    }
+
+    fn freq(samples: &[u8]) -> u32 {
+        let (periods, count) = periods(samples);
+        SAMPLE_RATE * count / periods.len() as u32
+    }
+
+    // per mille of the time high
+    fn duty(samples: &[u8]) -> u32 {
+        let (periods, _) = periods(samples);
+        let high: u32 = periods.iter().map(|&s| (s - LOW) as u32).sum();
+        high * 1000 / ((HIGH - LOW) as u32 * periods.len() as u32)
+    }
+
+    // what the mixer should give a note over `from..to` ms into it
+    fn expected_duty(note: &Note, from: u16, to: u16) -> u32 {
+        let sum: u32 = (from..to)
+            .map(|ms| note.duty as u32 * note.env.level(ms) as u32 / 10)
+            .sum();
+        sum / (to - from) as u32
+    }
+
+    fn near(value: u32, expected: u32, slack: u32) -> bool {
+        value + slack >= expected && value <= expected + slack
+    }
+
+    #[test]
+    fn shoot() {
+        let _audio = lock();
+        let (notes, samples) = render_sfx(Sfx::Shoot, 80);
+
+        // 3550 then 2900 Hz for 25 ms each, 150 Hz of jitter
+        assert_eq!(notes.len(), 2);
+        for (note, &base) in notes.iter().zip([3550u32, 2900].iter()) {
+            assert!(near(note.freq as u32, base, 150), "{}", note.freq);
+            assert_eq!((note.ms, note.duty), (25, 10));
+            assert!(note.env == ENV_PLUCK);
+        }
+
+        for (i, note) in notes.iter().enumerate() {
+            let from = i as u32 * 25;
+            let samples = window(&samples, from, from + 25);
+            assert!(sounding(samples));
+            let f = freq(samples);
+            assert!(near(f, note.freq as u32, note.freq as u32 / 100), "{}", f);
+            // 10 % at the start, the pluck takes it down from there
+            let start = duty(&samples[..5 * SAMPLES_PER_MS as usize]);
+            assert!(near(start, expected_duty(note, 0, 5), 5), "{}", start);
+            let end = duty(&samples[20 * SAMPLES_PER_MS as usize..]);
+            assert!(near(end, expected_duty(note, 20, 25), 5), "{}", end);
+        }
+        assert!(silent(window(&samples, 50, 80)));
+    }
+
+    #[test]
+    fn bomb() {
+        let _audio = lock();
+        let (notes, samples) = render_sfx(Sfx::Bomb, 340);
+
+        // three sweeps from 200 to 1600 Hz in 20 ms notes with a 20 ms
+        // rest after each one, 200 Hz of jitter
+        assert_eq!(notes.len(), 15);
+        for sweep in notes.chunks(5) {
+            for (note, &base) in sweep.iter().zip([200u32, 550, 900, 1250].iter()) {
+                assert!(near(note.freq as u32, base, 200), "{}", note.freq);
+                assert_eq!((note.ms, note.duty), (20, 40));
+                assert!(note.env == ENV_SWELL);
+            }
+            assert!(sweep[4].freq == 0 && sweep[4].ms == 20);
+        }
+
+        for (i, note) in notes.iter().enumerate() {
+            let from = i as u32 * 20;
+            if note.freq == 0 {
+                assert!(silent(window(&samples, from, from + 20)));
+                continue;
+            }
+            // the swell starts every note at zero duty
+            assert!(silent(window(&samples, from, from + 1)));
+            assert!(sounding(window(&samples, from + 1, from + 20)));
+            // 40 % once the swell is up after 15 ms. the swell moves the
+            // pulse starts while it rises, so it is measured after that,
+            // where the low notes are short of two periods
+            if note.freq >= 400 {
+                let full = window(&samples, from + 15, from + 20);
+                let f = freq(full);
+                assert!(
+                    near(f, note.freq as u32, note.freq as u32 / 100),
+                    "{} {}",
+                    note.freq,
+                    f
+                );
+                let d = duty(full);
+                assert!(near(d, 400, 10), "{} {}", note.freq, d);
+            }
+        }
+        assert!(silent(window(&samples, 300, 340)));
+    }
+}
@@ -0,0 +1,81 @@
+use super::*;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum FireMode {
+    // one shot a press
+    Tap,
+    // shoots while A is held
+    Auto(Repeat),
+    // a shot on release, charged when held this many frames
+    Charge(u16),
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Shot {
+    Normal,
+    // goes through the enemies it hits
+    Charged,
+}
+
+pub struct Weapon {
+    pub mode: FireMode,
+}
+
+pub const WEAPON_COUNT: u8 = 3;
+
+pub const WEAPONS: [Weapon; WEAPON_COUNT as usize] = [
+    // blaster
+    Weapon {
+        mode: FireMode::Tap,
+    },
+    // rapid
+    Weapon {
+        mode: FireMode::Auto(Repeat { delay: 6, every: 4 }),
+    },
+    // cannon
+    Weapon {
+        mode: FireMode::Charge(FRAME_HZ as u16 / 2),
+    },
+];
+
+// turns the A button events into shots for a weapon
+pub struct Trigger {
+    // frames A has been held, for the charge
+    held: u16,
+}
+
+impl Trigger {
+    pub fn new() -> Trigger {
+        Trigger { held: 0 }
+    }
+
+    // the repeat the weapon needs on A
+    pub fn repeat(weapon: &Weapon) -> Option<Repeat> {
+        match weapon.mode {
+            FireMode::Auto(repeat) => Some(repeat),
+            _ => None,
+        }
+    }
+
+    pub fn event(&mut self, weapon: &Weapon, event: ButtonEvent) -> Option<Shot> {
+        match (weapon.mode, event) {
+            (FireMode::Charge(_), ButtonEvent::Held(Button::A, frames)) => {
+                self.held = frames;
+                None
+            }
+            (FireMode::Charge(charge), ButtonEvent::Released(Button::A)) => {
+                let held = self.held;
+                self.held = 0;
+                if held >= charge {
+                    Some(Shot::Charged)
+                } else {
+                    Some(Shot::Normal)
+                }
+            }
+            (FireMode::Charge(_), _) => None,
+            (_, ButtonEvent::Pressed(Button::A)) => Some(Shot::Normal),
+            (FireMode::Auto(_), ButtonEvent::Repeat(Button::A)) => Some(Shot::Normal),
+            _ => None,
+        }
+    }
+}